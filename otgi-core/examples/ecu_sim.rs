@@ -0,0 +1,33 @@
+//! Simulates an engine ECU on a SocketCAN interface, e.g.
+//! `cargo run --example ecu_sim --features socketcan -- vcan0 scenarios/idle_to_cruise.txt`
+
+use otgi_core::{
+    sim::{EcuSimulator, Scenario},
+    socketcan::SocketCanTransport,
+};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let interface = args.next().unwrap_or_else(|| "vcan0".to_string());
+    let path = args
+        .next()
+        .unwrap_or_else(|| "scenarios/idle_to_cruise.txt".to_string());
+
+    let scenario: Scenario = match std::fs::read_to_string(&path) {
+        Ok(contents) => match contents.parse() {
+            Ok(scenario) => scenario,
+            Err(e) => panic!("{path}:{}: {}", e.line, e.message),
+        },
+        Err(e) => panic!("Couldn't read {path}: {e}"),
+    };
+
+    let mut transport = match SocketCanTransport::open(&interface) {
+        Ok(transport) => transport,
+        Err(e) => panic!("Couldn't open {interface}: {e}"),
+    };
+
+    println!("Simulating ECU on {interface} from {path}");
+    EcuSimulator::new(scenario)
+        .run(&mut transport, None)
+        .unwrap();
+}
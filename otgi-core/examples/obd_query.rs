@@ -0,0 +1,41 @@
+//! Polls a few PIDs over SocketCAN, e.g. `cargo run --example obd_query --features socketcan -- vcan0`
+
+use otgi_core::{
+    obd::{ObdDriver, ObdMode, ObdQuery, ObdReadableData, PID},
+    socketcan::SocketCanTransport,
+};
+
+fn main() {
+    let interface = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "can0".to_string());
+    let transport = match SocketCanTransport::open(&interface) {
+        Ok(transport) => transport,
+        Err(e) => panic!("Couldn't open {interface}: {e}"),
+    };
+
+    let mut driver = ObdDriver::try_new(transport, &Default::default()).unwrap();
+
+    for pid in [
+        PID::EngineSpeed,
+        PID::VehicleSpeed,
+        PID::MassAirFlow,
+        PID::ShortTermFuelTrimBankOne,
+        PID::LongTermFuelTrimBankOne,
+    ] {
+        match driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(pid))) {
+            Ok(value) => println!("{:?}: {:?}", pid, value),
+            Err(e) => println!("{:?}: {:?}", pid, e),
+        }
+    }
+
+    match driver.query(&ObdQuery::new(ObdMode::QueryDTC, None)) {
+        Ok(ObdReadableData::DTC(codes)) => {
+            for code in codes {
+                println!("DTC: {} {}", code, code.description().unwrap_or(""));
+            }
+        }
+        Ok(other) => println!("DTC: {:?}", other),
+        Err(e) => println!("DTC: {:?}", e),
+    }
+}
@@ -0,0 +1,214 @@
+use std::time::Duration;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CanId {
+    Standard(u16),
+    Extended(u32),
+}
+
+impl CanId {
+    pub fn raw(&self) -> u32 {
+        match *self {
+            Self::Standard(id) => id as u32,
+            Self::Extended(id) => id,
+        }
+    }
+
+    pub fn is_extended(&self) -> bool {
+        matches!(self, Self::Extended(_))
+    }
+}
+
+/// A classic CAN data frame (at most 8 data bytes)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CanFrame {
+    id: CanId,
+    len: u8,
+    data: [u8; 8],
+}
+
+impl CanFrame {
+    pub fn new(id: CanId, data: &[u8]) -> Option<Self> {
+        if data.len() > 8 {
+            return None;
+        }
+
+        let mut frame = Self {
+            id,
+            len: data.len() as u8,
+            data: [0; 8],
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+        Some(frame)
+    }
+
+    pub fn id(&self) -> CanId {
+        self.id
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+}
+
+/// Acceptance filter; a frame passes when every bit set in `mask` matches `id`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CanFilter {
+    pub id: u32,
+    pub mask: u32,
+    pub extended: bool,
+}
+
+impl CanFilter {
+    pub fn accept_all() -> Self {
+        Self {
+            id: 0,
+            mask: 0,
+            extended: false,
+        }
+    }
+
+    /// Accepts `id` only
+    pub fn exact(id: CanId) -> Self {
+        match id {
+            CanId::Standard(id) => Self {
+                id: id as u32,
+                mask: 0x7FF,
+                extended: false,
+            },
+            CanId::Extended(id) => Self {
+                id,
+                mask: 0x1FFF_FFFF,
+                extended: true,
+            },
+        }
+    }
+
+    pub fn matches(&self, id: CanId) -> bool {
+        // Accepting everything should include both identifier lengths
+        if self.mask == 0 {
+            return true;
+        }
+
+        id.is_extended() == self.extended && (id.raw() & self.mask) == (self.id & self.mask)
+    }
+
+    /// The ID and mask as little endian u32s followed by 1 for extended IDs, as sent over BLE
+    pub fn to_le_bytes(&self) -> [u8; 9] {
+        let mut bytes = [0; 9];
+        bytes[..4].copy_from_slice(&self.id.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.mask.to_le_bytes());
+        bytes[8] = self.extended as u8;
+        bytes
+    }
+
+    pub fn from_le_bytes(bytes: [u8; 9]) -> Option<Self> {
+        Some(Self {
+            id: u32::from_le_bytes(bytes[..4].try_into().unwrap()),
+            mask: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            extended: match bytes[8] {
+                0 => false,
+                1 => true,
+                _ => return None,
+            },
+        })
+    }
+}
+
+impl Default for CanFilter {
+    fn default() -> Self {
+        Self::accept_all()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BusState {
+    #[default]
+    Running,
+    /// Too many transmit errors; the controller has stopped taking part in bus traffic
+    BusOff,
+    Recovering,
+    Stopped,
+}
+
+/// Controller health as reported by the transport
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BusStatus {
+    pub state: BusState,
+    pub tx_error_counter: u32,
+    pub rx_error_counter: u32,
+}
+
+/// The minimal interface the OBD stack needs from a CAN controller
+pub trait CanTransport {
+    type Error: core::fmt::Debug;
+
+    fn transmit(&mut self, frame: &CanFrame, timeout: Duration) -> Result<(), Self::Error>;
+
+    /// Returns `Ok(None)` if no frame passing the filter arrived within `timeout`
+    fn receive(&mut self, timeout: Duration) -> Result<Option<CanFrame>, Self::Error>;
+
+    fn set_filter(&mut self, filter: CanFilter) -> Result<(), Self::Error>;
+
+    /// Controllers that can't report their state are assumed to be running
+    fn status(&mut self) -> Result<BusStatus, Self::Error> {
+        Ok(BusStatus::default())
+    }
+
+    /// Brings a bus-off controller back to running
+    fn recover(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<T: CanTransport + ?Sized> CanTransport for &mut T {
+    type Error = T::Error;
+
+    fn transmit(&mut self, frame: &CanFrame, timeout: Duration) -> Result<(), Self::Error> {
+        (**self).transmit(frame, timeout)
+    }
+
+    fn receive(&mut self, timeout: Duration) -> Result<Option<CanFrame>, Self::Error> {
+        (**self).receive(timeout)
+    }
+
+    fn set_filter(&mut self, filter: CanFilter) -> Result<(), Self::Error> {
+        (**self).set_filter(filter)
+    }
+
+    fn status(&mut self) -> Result<BusStatus, Self::Error> {
+        (**self).status()
+    }
+
+    fn recover(&mut self) -> Result<(), Self::Error> {
+        (**self).recover()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_filters() {
+        let filter = CanFilter {
+            id: 0x18DA_F100,
+            mask: 0x1FFF_FF00,
+            extended: true,
+        };
+        let bytes = filter.to_le_bytes();
+        assert_eq!(
+            bytes,
+            [0x00, 0xF1, 0xDA, 0x18, 0x00, 0xFF, 0xFF, 0x1F, 0x01]
+        );
+        assert_eq!(CanFilter::from_le_bytes(bytes), Some(filter));
+
+        let mut bytes = CanFilter::accept_all().to_le_bytes();
+        assert_eq!(
+            CanFilter::from_le_bytes(bytes),
+            Some(CanFilter::accept_all())
+        );
+        bytes[8] = 2;
+        assert_eq!(CanFilter::from_le_bytes(bytes), None);
+    }
+}
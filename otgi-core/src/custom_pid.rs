@@ -0,0 +1,272 @@
+use crate::{
+    can::CanId,
+    formula::Formula,
+    isotp,
+    units::{Quantity, Unit},
+};
+
+/// Where and why a definition file failed to parse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// A PID that isn't part of the [`PID`](crate::obd::PID) enum, such as a manufacturer DID, read
+/// with [`ObdDriver::query_custom`](crate::obd::ObdDriver::query_custom)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomPid {
+    pub name: String,
+    pub short_name: String,
+    /// Service ID followed by the PID or DID, e.g. `[0x22, 0x11, 0xBD]`. The response echoes
+    /// these bytes, and `A` in the formula is the byte after them.
+    pub request: Vec<u8>,
+    pub formula: Formula,
+    /// Decoded values outside `min` and `max` are taken for a misread response
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    /// Units that aren't recognised are left as a plain [`Unit::Count`]
+    pub unit: Unit,
+    /// Physical request ID of the ECU to ask, or every ECU if `None`
+    pub header: Option<CanId>,
+}
+
+impl CustomPid {
+    /// The ID the ECU in `header` answers on
+    pub fn response_id(&self) -> Option<CanId> {
+        self.header.and_then(isotp::physical_response_id)
+    }
+
+    /// Decodes the data bytes following the echoed request, `None` if they're too short or
+    /// decode outside `min` and `max`
+    pub fn decode(&self, data: &[u8]) -> Option<Quantity> {
+        self.formula
+            .eval(data)
+            .filter(|&value| {
+                self.min.map_or(true, |min| value >= min)
+                    && self.max.map_or(true, |max| value <= max)
+            })
+            .map(|value| Quantity::new(value, self.unit))
+    }
+
+    /// Parses Torque style CSV, one PID per line: `Name,ShortName,ModeAndPID,Equation,Min Value,
+    /// Max Value,Units,Header`, where ModeAndPID and Header are hex and the last four fields may
+    /// be left empty. Fields may be double quoted to hold commas. Blank lines, `#` comments and a
+    /// header row naming the columns before the first definition are skipped.
+    pub fn parse_csv(s: &str) -> Result<Vec<CustomPid>, DefinitionError> {
+        let mut pids = vec![];
+        let mut first = true;
+
+        for (i, line) in s.lines().enumerate() {
+            let error = |message: &str| DefinitionError {
+                line: i + 1,
+                message: message.to_string(),
+            };
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = split_fields(line).ok_or_else(|| error("unterminated quote"))?;
+            if std::mem::take(&mut first) && fields.get(..3).is_some_and(|names| names == HEADER) {
+                continue;
+            }
+            if !(4..=8).contains(&fields.len()) {
+                return Err(error("expected 4 to 8 comma separated fields"));
+            }
+            let field = |i: usize| fields.get(i).map_or("", String::as_str);
+
+            let request = parse_hex_bytes(field(2)).ok_or_else(|| error("invalid mode and PID"))?;
+            let formula: Formula = field(3)
+                .parse()
+                .map_err(|e| error(&format!("invalid equation: {}", e)))?;
+            let limit = |field: &str| match field {
+                "" => Ok(None),
+                limit => limit
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| error("invalid min or max value")),
+            };
+            // Units are free text in Torque
+            let unit = Unit::from_symbol(field(6)).unwrap_or(Unit::Count);
+            let header = match field(7) {
+                "" => None,
+                header => Some(
+                    parse_header(header)
+                        .filter(|&header| isotp::physical_response_id(header).is_some())
+                        .ok_or_else(|| error("invalid header"))?,
+                ),
+            };
+
+            pids.push(CustomPid {
+                name: field(0).to_string(),
+                short_name: field(1).to_string(),
+                request,
+                formula,
+                min: limit(field(4))?,
+                max: limit(field(5))?,
+                unit,
+                header,
+            });
+        }
+
+        Ok(pids)
+    }
+}
+
+/// The first columns of Torque's header row
+const HEADER: [&str; 3] = ["Name", "ShortName", "ModeAndPID"];
+
+/// Splits a CSV line on commas outside double quotes, where `""` is a literal quote. `None` if a
+/// quote is left open.
+fn split_fields(line: &str) -> Option<Vec<String>> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+
+    (!quoted).then_some(fields)
+}
+
+fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if hex.is_empty() || hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Up to three hex digits is an 11 bit ID, anything longer 29 bit
+fn parse_header(hex: &str) -> Option<CanId> {
+    let id = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        1..=3 if id <= 0x7FF => Some(CanId::Standard(id as u16)),
+        4..=8 if id <= 0x1FFF_FFFF => Some(CanId::Extended(id)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_torque_csv() {
+        let pids = CustomPid::parse_csv(
+            "Name,ShortName,ModeAndPID,Equation,Min Value,Max Value,Units,Header\n\
+             # Transmission\n\
+             Trans Temp,TFT,221E1C,((A*256)+B)/16,-40,150,°C,7E1\n\
+             \n\
+             Boost,Boost,010B,A-100,,,kPa\n\
+             Hybrid SOC,SOC,225B3F,A*20/51,0,100,%,18DA10F1\n",
+        )
+        .unwrap();
+
+        assert_eq!(pids.len(), 3);
+        assert_eq!(pids[0].name, "Trans Temp");
+        assert_eq!(pids[0].request, [0x22, 0x1E, 0x1C]);
+        assert_eq!(pids[0].min, Some(-40.0));
+        assert_eq!(pids[0].header, Some(CanId::Standard(0x7E1)));
+        assert_eq!(
+            pids[0].decode(&[0x05, 0x20]),
+            Some(Quantity::new(82.0, Unit::Celsius))
+        );
+        assert_eq!(pids[1].max, None);
+        assert_eq!(pids[1].header, None);
+        assert_eq!(pids[2].header, Some(CanId::Extended(0x18DA_10F1)));
+    }
+
+    #[test]
+    fn unknown_units_are_counts() {
+        let pids =
+            CustomPid::parse_csv("Gear,Gear,221E12,A,,,gear\nAFR,AFR,2211A0,A/10,,,ratio").unwrap();
+
+        assert_eq!(pids[0].unit, Unit::Count);
+        assert_eq!(pids[1].unit, Unit::Count);
+    }
+
+    #[test]
+    fn limits_reject_values() {
+        let pid = &CustomPid::parse_csv("Trans Temp,TFT,221E1C,A-40,-40,150,°C").unwrap()[0];
+
+        assert_eq!(pid.decode(&[0]), Some(Quantity::new(-40.0, Unit::Celsius)));
+        assert_eq!(
+            pid.decode(&[190]),
+            Some(Quantity::new(150.0, Unit::Celsius))
+        );
+        assert_eq!(pid.decode(&[191]), None);
+    }
+
+    #[test]
+    fn only_skips_the_header_row() {
+        let pids = CustomPid::parse_csv(
+            "# Exported by Torque\n\
+             Name,ShortName,ModeAndPID,Equation,Min Value,Max Value,Units,Header\n\
+             Name Plate Voltage,NPV,22F40D,A/10,,,V\n",
+        )
+        .unwrap();
+
+        assert_eq!(pids.len(), 1);
+        assert_eq!(pids[0].name, "Name Plate Voltage");
+
+        // Only before the first definition
+        let error =
+            CustomPid::parse_csv("Boost,B,010B,A\nName,ShortName,ModeAndPID,Equation").unwrap_err();
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn parses_quoted_fields() {
+        let pids =
+            CustomPid::parse_csv("\"Temp, Trans \"\"A\"\"\",TFT,221E1C,\"SIGNED(A) + 40\",,,°C\n")
+                .unwrap();
+
+        assert_eq!(pids[0].name, "Temp, Trans \"A\"");
+        assert_eq!(
+            pids[0].decode(&[0xFE]),
+            Some(Quantity::new(38.0, Unit::Celsius))
+        );
+        assert_eq!(
+            CustomPid::parse_csv("\"Temp,TFT,221E1C,A")
+                .unwrap_err()
+                .message,
+            "unterminated quote"
+        );
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let error = |csv: &str| CustomPid::parse_csv(csv).unwrap_err();
+
+        assert_eq!(error("a,b,22,A\nc,d,2211B,A").line, 2);
+        assert_eq!(
+            error("a,b,22").message,
+            "expected 4 to 8 comma separated fields"
+        );
+        assert_eq!(
+            error("a,b,22,A+").message,
+            "invalid equation: unexpected end of formula at 2"
+        );
+        assert_eq!(error("a,b,22,A,x").message, "invalid min or max value");
+        assert_eq!(error("a,b,22,A,,,,800").message, "invalid header");
+        // Nothing answers on a known ID
+        assert_eq!(error("a,b,22,A,,,,123").message, "invalid header");
+        assert_eq!(error("a,b,22,A,,,,18DB33F1").message, "invalid header");
+    }
+}
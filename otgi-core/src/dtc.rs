@@ -0,0 +1,307 @@
+use core::fmt;
+use std::str::FromStr;
+
+/// The system a trouble code belongs to, given by its leading letter
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DtcSystem {
+    Powertrain,
+    Chassis,
+    Body,
+    Network,
+}
+
+impl DtcSystem {
+    pub fn letter(&self) -> char {
+        match self {
+            Self::Powertrain => 'P',
+            Self::Chassis => 'C',
+            Self::Body => 'B',
+            Self::Network => 'U',
+        }
+    }
+}
+
+/// A two byte trouble code as reported by modes 02, 03, 07 and 0A, displayed as e.g. "P0301"
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DiagnosticTroubleCode(u16);
+
+impl DiagnosticTroubleCode {
+    pub fn from_bytes(bytes: [u8; 2]) -> Self {
+        Self(u16::from_be_bytes(bytes))
+    }
+
+    pub fn to_bytes(&self) -> [u8; 2] {
+        self.0.to_be_bytes()
+    }
+
+    pub fn system(&self) -> DtcSystem {
+        match self.0 >> 14 {
+            0 => DtcSystem::Powertrain,
+            1 => DtcSystem::Chassis,
+            2 => DtcSystem::Body,
+            _ => DtcSystem::Network,
+        }
+    }
+
+    /// Whether the code is defined by SAE J2012 rather than the manufacturer
+    pub fn is_generic(&self) -> bool {
+        // The first digit selects the range, P3 is split between manufacturer (P30-P33) and SAE
+        match (self.system(), (self.0 >> 12) & 0x3) {
+            (_, 0) => true,
+            (DtcSystem::Powertrain, 1) => false,
+            (DtcSystem::Powertrain, 2) => true,
+            (DtcSystem::Powertrain, _) => (self.0 >> 8) & 0xF >= 4,
+            (_, digit) => digit == 3,
+        }
+    }
+
+    /// Description of generic codes from the built in table
+    pub fn description(&self) -> Option<&'static str> {
+        if !self.is_generic() {
+            return None;
+        }
+
+        DESCRIPTIONS
+            .binary_search_by_key(&self.0, |&(code, _)| code)
+            .ok()
+            .map(|i| DESCRIPTIONS[i].1)
+    }
+}
+
+impl fmt::Display for DiagnosticTroubleCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{:04X}", self.system().letter(), self.0 & 0x3FFF)
+    }
+}
+
+impl FromStr for DiagnosticTroubleCode {
+    type Err = ();
+
+    /// Parses a code such as "P0301"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let system: u16 = match chars.next().ok_or(())?.to_ascii_uppercase() {
+            'P' => 0,
+            'C' => 1,
+            'B' => 2,
+            'U' => 3,
+            _ => return Err(()),
+        };
+
+        let digits = chars.as_str();
+        if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(());
+        }
+        let code = u16::from_str_radix(digits, 16).map_err(|_| ())?;
+        if code > 0x3FFF {
+            return Err(());
+        }
+
+        Ok(Self(system << 14 | code))
+    }
+}
+
+// Sorted by code so it can be binary searched
+#[rustfmt::skip]
+const DESCRIPTIONS: &[(u16, &str)] = &[
+    (0x0010, "Intake camshaft position actuator circuit (bank 1)"),
+    (0x0011, "Intake camshaft position timing over-advanced or system performance (bank 1)"),
+    (0x0012, "Intake camshaft position timing over-retarded (bank 1)"),
+    (0x0013, "Exhaust camshaft position actuator circuit (bank 1)"),
+    (0x0014, "Exhaust camshaft position timing over-advanced or system performance (bank 1)"),
+    (0x0016, "Crankshaft position - camshaft position correlation (bank 1 sensor A)"),
+    (0x0030, "HO2S heater control circuit (bank 1 sensor 1)"),
+    (0x0036, "HO2S heater control circuit (bank 1 sensor 2)"),
+    (0x0087, "Fuel rail/system pressure too low"),
+    (0x0088, "Fuel rail/system pressure too high"),
+    (0x0100, "Mass or volume air flow circuit"),
+    (0x0101, "Mass or volume air flow circuit range/performance"),
+    (0x0102, "Mass or volume air flow circuit low input"),
+    (0x0103, "Mass or volume air flow circuit high input"),
+    (0x0105, "Manifold absolute pressure/barometric pressure circuit"),
+    (0x0106, "Manifold absolute pressure/barometric pressure circuit range/performance"),
+    (0x0107, "Manifold absolute pressure/barometric pressure circuit low input"),
+    (0x0108, "Manifold absolute pressure/barometric pressure circuit high input"),
+    (0x0110, "Intake air temperature circuit"),
+    (0x0111, "Intake air temperature circuit range/performance"),
+    (0x0112, "Intake air temperature circuit low input"),
+    (0x0113, "Intake air temperature circuit high input"),
+    (0x0115, "Engine coolant temperature circuit"),
+    (0x0116, "Engine coolant temperature circuit range/performance"),
+    (0x0117, "Engine coolant temperature circuit low input"),
+    (0x0118, "Engine coolant temperature circuit high input"),
+    (0x0120, "Throttle/pedal position sensor/switch A circuit"),
+    (0x0121, "Throttle/pedal position sensor/switch A circuit range/performance"),
+    (0x0122, "Throttle/pedal position sensor/switch A circuit low input"),
+    (0x0123, "Throttle/pedal position sensor/switch A circuit high input"),
+    (0x0125, "Insufficient coolant temperature for closed loop fuel control"),
+    (0x0128, "Coolant thermostat (coolant temperature below thermostat regulating temperature)"),
+    (0x0130, "O2 sensor circuit (bank 1 sensor 1)"),
+    (0x0131, "O2 sensor circuit low voltage (bank 1 sensor 1)"),
+    (0x0132, "O2 sensor circuit high voltage (bank 1 sensor 1)"),
+    (0x0133, "O2 sensor circuit slow response (bank 1 sensor 1)"),
+    (0x0134, "O2 sensor circuit no activity detected (bank 1 sensor 1)"),
+    (0x0135, "O2 sensor heater circuit (bank 1 sensor 1)"),
+    (0x0136, "O2 sensor circuit (bank 1 sensor 2)"),
+    (0x0137, "O2 sensor circuit low voltage (bank 1 sensor 2)"),
+    (0x0138, "O2 sensor circuit high voltage (bank 1 sensor 2)"),
+    (0x0141, "O2 sensor heater circuit (bank 1 sensor 2)"),
+    (0x0150, "O2 sensor circuit (bank 2 sensor 1)"),
+    (0x0155, "O2 sensor heater circuit (bank 2 sensor 1)"),
+    (0x0156, "O2 sensor circuit (bank 2 sensor 2)"),
+    (0x0161, "O2 sensor heater circuit (bank 2 sensor 2)"),
+    (0x0171, "System too lean (bank 1)"),
+    (0x0172, "System too rich (bank 1)"),
+    (0x0174, "System too lean (bank 2)"),
+    (0x0175, "System too rich (bank 2)"),
+    (0x0191, "Fuel rail pressure sensor A circuit range/performance"),
+    (0x0201, "Injector circuit/open - cylinder 1"),
+    (0x0202, "Injector circuit/open - cylinder 2"),
+    (0x0203, "Injector circuit/open - cylinder 3"),
+    (0x0204, "Injector circuit/open - cylinder 4"),
+    (0x0205, "Injector circuit/open - cylinder 5"),
+    (0x0206, "Injector circuit/open - cylinder 6"),
+    (0x0217, "Engine coolant over temperature condition"),
+    (0x0218, "Transmission fluid over temperature condition"),
+    (0x0219, "Engine overspeed condition"),
+    (0x0230, "Fuel pump primary circuit"),
+    (0x0234, "Turbocharger/supercharger A overboost condition"),
+    (0x0299, "Turbocharger/supercharger A underboost condition"),
+    (0x0300, "Random/multiple cylinder misfire detected"),
+    (0x0301, "Cylinder 1 misfire detected"),
+    (0x0302, "Cylinder 2 misfire detected"),
+    (0x0303, "Cylinder 3 misfire detected"),
+    (0x0304, "Cylinder 4 misfire detected"),
+    (0x0305, "Cylinder 5 misfire detected"),
+    (0x0306, "Cylinder 6 misfire detected"),
+    (0x0307, "Cylinder 7 misfire detected"),
+    (0x0308, "Cylinder 8 misfire detected"),
+    (0x0325, "Knock sensor 1 circuit (bank 1 or single sensor)"),
+    (0x0327, "Knock sensor 1 circuit low (bank 1 or single sensor)"),
+    (0x0335, "Crankshaft position sensor A circuit"),
+    (0x0336, "Crankshaft position sensor A circuit range/performance"),
+    (0x0340, "Camshaft position sensor A circuit (bank 1 or single sensor)"),
+    (0x0341, "Camshaft position sensor A circuit range/performance (bank 1 or single sensor)"),
+    (0x0351, "Ignition coil A primary/secondary circuit"),
+    (0x0352, "Ignition coil B primary/secondary circuit"),
+    (0x0353, "Ignition coil C primary/secondary circuit"),
+    (0x0354, "Ignition coil D primary/secondary circuit"),
+    (0x0400, "Exhaust gas recirculation flow"),
+    (0x0401, "Exhaust gas recirculation flow insufficient detected"),
+    (0x0402, "Exhaust gas recirculation flow excessive detected"),
+    (0x0403, "Exhaust gas recirculation control circuit"),
+    (0x0410, "Secondary air injection system"),
+    (0x0411, "Secondary air injection system incorrect flow detected"),
+    (0x0420, "Catalyst system efficiency below threshold (bank 1)"),
+    (0x0421, "Warm up catalyst efficiency below threshold (bank 1)"),
+    (0x0430, "Catalyst system efficiency below threshold (bank 2)"),
+    (0x0440, "Evaporative emission system"),
+    (0x0441, "Evaporative emission system incorrect purge flow"),
+    (0x0442, "Evaporative emission system leak detected (small leak)"),
+    (0x0443, "Evaporative emission system purge control valve circuit"),
+    (0x0446, "Evaporative emission system vent control circuit"),
+    (0x0449, "Evaporative emission system vent valve/solenoid circuit"),
+    (0x0451, "Evaporative emission system pressure sensor/switch range/performance"),
+    (0x0452, "Evaporative emission system pressure sensor/switch low"),
+    (0x0453, "Evaporative emission system pressure sensor/switch high"),
+    (0x0455, "Evaporative emission system leak detected (large leak)"),
+    (0x0456, "Evaporative emission system leak detected (very small leak)"),
+    (0x0457, "Evaporative emission system leak detected (fuel cap loose/off)"),
+    (0x0460, "Fuel level sensor A circuit"),
+    (0x0480, "Fan 1 control circuit"),
+    (0x0500, "Vehicle speed sensor A"),
+    (0x0505, "Idle air control system"),
+    (0x0506, "Idle air control system RPM lower than expected"),
+    (0x0507, "Idle air control system RPM higher than expected"),
+    (0x0520, "Engine oil pressure sensor/switch circuit"),
+    (0x0562, "System voltage low"),
+    (0x0563, "System voltage high"),
+    (0x0600, "Serial communication link"),
+    (0x0601, "Internal control module memory check sum error"),
+    (0x0606, "Control module processor"),
+    (0x0700, "Transmission control system (MIL request)"),
+    (0x0705, "Transmission range sensor A circuit (PRNDL input)"),
+    (0x0715, "Input/turbine speed sensor A circuit"),
+    (0x0720, "Output speed sensor circuit"),
+    (0x0740, "Torque converter clutch solenoid circuit/open"),
+    (0x0750, "Shift solenoid A"),
+    (0x0755, "Shift solenoid B"),
+    (0x2096, "Post catalyst fuel trim system too lean (bank 1)"),
+    (0x2097, "Post catalyst fuel trim system too rich (bank 1)"),
+    (0x2135, "Throttle/pedal position sensor/switch A/B voltage correlation"),
+    (0xC001, "High speed CAN communication bus"),
+    (0xC100, "Lost communication with ECM/PCM A"),
+    (0xC101, "Lost communication with TCM"),
+    (0xC121, "Lost communication with anti-lock brake system control module"),
+    (0xC140, "Lost communication with body control module"),
+    (0xC155, "Lost communication with instrument panel cluster control module"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dtc(code: &str) -> DiagnosticTroubleCode {
+        code.parse().unwrap()
+    }
+
+    #[test]
+    fn decodes_bytes() {
+        let cases = [
+            ([0x03, 0x01], "P0301", DtcSystem::Powertrain),
+            ([0x01, 0x43], "P0143", DtcSystem::Powertrain),
+            ([0x52, 0x34], "C1234", DtcSystem::Chassis),
+            ([0x8A, 0x1F], "B0A1F", DtcSystem::Body),
+            ([0xC1, 0x00], "U0100", DtcSystem::Network),
+            ([0xFF, 0xFF], "U3FFF", DtcSystem::Network),
+        ];
+
+        for (bytes, code, system) in cases {
+            let decoded = DiagnosticTroubleCode::from_bytes(bytes);
+            assert_eq!(decoded.to_string(), code);
+            assert_eq!(decoded.system(), system);
+            assert_eq!(decoded.to_bytes(), bytes);
+            assert_eq!(dtc(code), decoded);
+        }
+    }
+
+    #[test]
+    fn parses_codes() {
+        assert_eq!(dtc("b0a1F").to_bytes(), [0x8A, 0x1F]);
+        assert!("P4000".parse::<DiagnosticTroubleCode>().is_err());
+        assert!("X0301".parse::<DiagnosticTroubleCode>().is_err());
+        assert!("P030".parse::<DiagnosticTroubleCode>().is_err());
+        assert!("P03011".parse::<DiagnosticTroubleCode>().is_err());
+        assert!("P+301".parse::<DiagnosticTroubleCode>().is_err());
+        assert!("".parse::<DiagnosticTroubleCode>().is_err());
+    }
+
+    #[test]
+    fn distinguishes_generic_codes() {
+        for code in ["P0301", "P2096", "P3400", "C0035", "B3000", "U0100"] {
+            assert!(dtc(code).is_generic(), "{}", code);
+        }
+        for code in ["P1301", "P3000", "P33FF", "C1234", "B2000", "U1000"] {
+            assert!(!dtc(code).is_generic(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn describes_generic_codes() {
+        assert_eq!(
+            dtc("P0420").description(),
+            Some("Catalyst system efficiency below threshold (bank 1)")
+        );
+        assert_eq!(
+            dtc("U0100").description(),
+            Some("Lost communication with ECM/PCM A")
+        );
+        assert_eq!(dtc("P1420").description(), None);
+        assert_eq!(dtc("P0FFF").description(), None);
+
+        assert!(DESCRIPTIONS.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(DESCRIPTIONS
+            .iter()
+            .all(|&(code, _)| DiagnosticTroubleCode(code).is_generic()));
+    }
+}
@@ -0,0 +1,472 @@
+use core::fmt;
+use std::str::FromStr;
+
+/// Where and why a formula failed to parse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormulaError {
+    /// Byte offset into the formula
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    And,
+    Or,
+    Shl,
+    Shr,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Function {
+    /// 8 bit two's complement
+    Signed,
+    /// 16 bit two's complement
+    Signed16,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "SIGNED" => Some(Function::Signed),
+            "SIGNED16" => Some(Function::Signed16),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(f32),
+    /// Response data byte, A being 0
+    Byte(usize),
+    /// A single bit of a data byte, 0 being the least significant
+    Bit(usize, u8),
+    Neg(Box<Expr>),
+    Call(Function, Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+/// Bitwise operators only take whole numbers that fit in 32 bits
+fn integer(value: f32) -> Option<u32> {
+    (value >= 0.0 && value <= u32::MAX as f32 && value.fract() == 0.0).then_some(value as u32)
+}
+
+impl Expr {
+    fn eval(&self, data: &[u8]) -> Option<f32> {
+        Some(match self {
+            Expr::Number(n) => *n,
+            Expr::Byte(i) => *data.get(*i)? as f32,
+            Expr::Bit(i, bit) => (data.get(*i)? >> bit & 1) as f32,
+            Expr::Neg(e) => -e.eval(data)?,
+            Expr::Call(function, e) => {
+                let value = integer(e.eval(data)?)?;
+                match function {
+                    Function::Signed => u8::try_from(value).ok()? as i8 as f32,
+                    Function::Signed16 => u16::try_from(value).ok()? as i16 as f32,
+                }
+            }
+            Expr::Binary(op, l, r) => {
+                let (l, r) = (l.eval(data)?, r.eval(data)?);
+                match op {
+                    Op::Add => l + r,
+                    Op::Sub => l - r,
+                    Op::Mul => l * r,
+                    Op::Div => l / r,
+                    Op::And => (integer(l)? & integer(r)?) as f32,
+                    Op::Or => (integer(l)? | integer(r)?) as f32,
+                    Op::Shl => integer(l)?.checked_shl(integer(r)?)? as f32,
+                    Op::Shr => integer(l)?.checked_shr(integer(r)?)? as f32,
+                }
+            }
+        })
+    }
+
+    fn bytes_needed(&self) -> usize {
+        match self {
+            Expr::Number(_) => 0,
+            Expr::Byte(i) | Expr::Bit(i, _) => i + 1,
+            Expr::Neg(e) | Expr::Call(_, e) => e.bytes_needed(),
+            Expr::Binary(_, l, r) => l.bytes_needed().max(r.bytes_needed()),
+        }
+    }
+}
+
+/// A formula over the data bytes of a response, written the way Torque and most PID lists do:
+/// `A` is the first byte after the echoed request, `B` the next and so on, and `{A:7}` is the
+/// most significant bit of `A`. They combine with `+ - * /`, the bitwise `& | << >>`,
+/// parentheses, decimal or `0x` hexadecimal numbers and `SIGNED(x)` or `SIGNED16(x)` to read a
+/// byte or word as two's complement, e.g. `(A*256+B)/10` or `SIGNED16(A*256+B)/4`.
+///
+/// Operators have the same precedence as in C. Evaluation is in `f32`; bitwise operators and
+/// the signed conversions fail on anything but whole numbers in range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Formula {
+    expr: Expr,
+}
+
+impl Formula {
+    /// Evaluates the formula, or `None` if `data` is too short, an operand is out of range for
+    /// its operator or the result isn't finite (such as after dividing by zero)
+    pub fn eval(&self, data: &[u8]) -> Option<f32> {
+        self.expr.eval(data).filter(|value| value.is_finite())
+    }
+
+    /// How many data bytes the formula reads
+    pub fn bytes_needed(&self) -> usize {
+        self.expr.bytes_needed()
+    }
+}
+
+impl FromStr for Formula {
+    type Err = FormulaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            input: s,
+            pos: 0,
+            depth: 0,
+            operators: 0,
+        };
+        let expr = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.pos < s.len() {
+            return Err(parser.error("unexpected character"));
+        }
+        Ok(Self { expr })
+    }
+}
+
+/// Deepest nesting of parentheses, calls and unary minus, as parsing and evaluating recurse once
+/// per level
+const MAX_DEPTH: usize = 32;
+/// Most binary operators in one formula, as each can add a level to the expression tree
+const MAX_OPERATORS: usize = 64;
+
+/// Binary operators from the loosest binding level to the tightest
+const LEVELS: [&[(&str, Op)]; 5] = [
+    &[("|", Op::Or)],
+    &[("&", Op::And)],
+    &[("<<", Op::Shl), (">>", Op::Shr)],
+    &[("+", Op::Add), ("-", Op::Sub)],
+    &[("*", Op::Mul), ("/", Op::Div)],
+];
+
+/// Precedence climbing over [`LEVELS`], with `unary = '-' unary | primary` and `primary =
+/// number | byte | '{' byte ':' bit '}' | function '(' expr ')' | '(' expr ')'`
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    depth: usize,
+    operators: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> FormulaError {
+        FormulaError {
+            position: self.pos,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn rest(&mut self) -> &str {
+        self.skip_whitespace();
+        &self.input[self.pos..]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), FormulaError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", token)))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, FormulaError> {
+        self.binary(0)
+    }
+
+    /// Parses with `parse` one nesting level deeper
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expr, FormulaError>,
+    ) -> Result<Expr, FormulaError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, FormulaError> {
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+
+        let mut expr = self.binary(level + 1)?;
+        'outer: loop {
+            for &(token, op) in ops.iter() {
+                if self.eat(token) {
+                    self.operators += 1;
+                    if self.operators > MAX_OPERATORS {
+                        return Err(self.error("too many operators"));
+                    }
+                    expr = Expr::Binary(op, Box::new(expr), Box::new(self.binary(level + 1)?));
+                    continue 'outer;
+                }
+            }
+            return Ok(expr);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, FormulaError> {
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.nested(Self::unary)?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, FormulaError> {
+        let start = self.pos;
+        match self.rest().chars().next() {
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.nested(Self::expr)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some('{') => {
+                self.pos += 1;
+                let byte = match self.name() {
+                    Some(name) => byte_index(name).ok_or_else(|| self.error("expected a byte"))?,
+                    None => return Err(self.error("expected a byte")),
+                };
+                self.expect(":")?;
+                let bit = match self.rest().chars().next() {
+                    Some(c @ '0'..='7') => c as u8 - b'0',
+                    _ => return Err(self.error("expected a bit from 0 to 7")),
+                };
+                self.pos += 1;
+                self.expect("}")?;
+                Ok(Expr::Bit(byte, bit))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let name = self.name().unwrap();
+                if let Some(byte) = byte_index(name) {
+                    return Ok(Expr::Byte(byte));
+                }
+                let Some(function) = Function::from_name(name) else {
+                    self.pos = start;
+                    self.skip_whitespace();
+                    return Err(self.error("unknown name"));
+                };
+                self.expect("(")?;
+                let argument = self.nested(Self::expr)?;
+                self.expect(")")?;
+                Ok(Expr::Call(function, Box::new(argument)))
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(_) => Err(self.error("expected a number, byte or '('")),
+            None => Err(self.error("unexpected end of formula")),
+        }
+    }
+
+    /// An identifier, such as a byte or function name
+    fn name(&mut self) -> Option<&str> {
+        let rest = self.rest();
+        if !rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let start = self.pos;
+        self.pos += len;
+        Some(&self.input[start..self.pos])
+    }
+
+    fn number(&mut self) -> Result<Expr, FormulaError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.'))
+            .unwrap_or(rest.len());
+        let literal = &rest[..len];
+
+        let value = match literal
+            .strip_prefix("0x")
+            .or_else(|| literal.strip_prefix("0X"))
+        {
+            Some(hex) => u32::from_str_radix(hex, 16).ok().map(|n| n as f32),
+            None => literal.parse().ok(),
+        };
+        let value = value.ok_or_else(|| self.error("invalid number"))?;
+        self.pos += len;
+        Ok(Expr::Number(value))
+    }
+}
+
+/// `A` to `Z` name the first 26 data bytes
+fn byte_index(name: &str) -> Option<usize> {
+    match name.as_bytes() {
+        &[c @ b'A'..=b'Z'] => Some((c - b'A') as usize),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(formula: &str) -> Formula {
+        formula.parse().unwrap()
+    }
+
+    fn eval(formula: &str, data: &[u8]) -> Option<f32> {
+        parse(formula).eval(data)
+    }
+
+    #[test]
+    fn evaluates_arithmetic() {
+        assert_eq!(eval("(A*256+B)/4", &[0x1A, 0xF8]), Some(1726.0));
+        assert_eq!(eval("A-40", &[0x7B]), Some(83.0));
+        assert_eq!(eval("A*100/255", &[0xFF]), Some(100.0));
+        assert_eq!(eval("-A + 0x10 * 2.5", &[4]), Some(36.0));
+        assert_eq!(eval("--A", &[4]), Some(4.0));
+        // Left associative
+        assert_eq!(eval("100 / 10 / 2 - 1 - 1", &[]), Some(3.0));
+        assert_eq!(eval("Z", &[7; 26]), Some(7.0));
+    }
+
+    #[test]
+    fn follows_c_precedence() {
+        assert_eq!(eval("1 + 2 * 3", &[]), Some(7.0));
+        assert_eq!(eval("1 << 2 + 1", &[]), Some(8.0));
+        assert_eq!(eval("A & 0xF0 >> 4", &[0xFF]), Some(15.0));
+        assert_eq!(eval("(A & 0xF0) >> 4", &[0xAB]), Some(10.0));
+        assert_eq!(eval("A | B & 1", &[2, 3]), Some(3.0));
+    }
+
+    #[test]
+    fn extracts_every_bit() {
+        let bits: Vec<Formula> = (0..8).map(|bit| parse(&format!("{{A:{}}}", bit))).collect();
+        let shifts: Vec<Formula> = (0..8)
+            .map(|bit| parse(&format!("B >> {} & 1", bit)))
+            .collect();
+        let repeated = parse("A << 8 | A");
+
+        for byte in 0..=u8::MAX {
+            for bit in 0..8 {
+                let expected = Some((byte >> bit & 1) as f32);
+                assert_eq!(bits[bit].eval(&[byte]), expected);
+                assert_eq!(shifts[bit].eval(&[0, byte]), expected);
+            }
+            assert_eq!(repeated.eval(&[byte]), Some(byte as f32 * 257.0));
+        }
+    }
+
+    #[test]
+    fn converts_every_signed_value() {
+        let signed = parse("SIGNED(A)");
+        for byte in 0..=u8::MAX {
+            assert_eq!(signed.eval(&[byte]), Some(byte as i8 as f32));
+        }
+
+        let signed16 = parse("SIGNED16(A*256+B)");
+        let high_byte = parse("signed(A)*256+B");
+        for word in 0..=u16::MAX {
+            let expected = Some(word as i16 as f32);
+            assert_eq!(signed16.eval(&word.to_be_bytes()), expected);
+            assert_eq!(high_byte.eval(&word.to_be_bytes()), expected);
+        }
+
+        // Out of range for the conversion
+        assert_eq!(eval("SIGNED(A*256)", &[1]), None);
+        assert_eq!(eval("SIGNED16(A*65536)", &[1]), None);
+        assert_eq!(eval("SIGNED(-1)", &[]), None);
+    }
+
+    #[test]
+    fn rejects_out_of_range_operands() {
+        assert_eq!(eval("A/2 & 1", &[3]), None);
+        assert_eq!(eval("-1 | 0", &[]), None);
+        assert_eq!(eval("1 << 32", &[]), None);
+        assert_eq!(eval("A/B", &[1, 0]), None);
+    }
+
+    #[test]
+    fn needs_enough_data() {
+        let formula = parse("((A*256)+B)/16");
+        assert_eq!(formula.bytes_needed(), 2);
+        assert_eq!(formula.eval(&[0x01]), None);
+        assert_eq!(parse("{D:0} + SIGNED(B)").bytes_needed(), 4);
+        assert_eq!(parse("1").bytes_needed(), 0);
+    }
+
+    #[test]
+    fn rejects_invalid_formulas() {
+        let error = |formula: &str| formula.parse::<Formula>().unwrap_err();
+        assert_eq!(error("").position, 0);
+        assert_eq!(error("(A+B").position, 4);
+        assert_eq!(error("A+*B").position, 2);
+        assert_eq!(error("1.2.3").position, 0);
+        assert_eq!(error("A B").position, 2);
+        assert_eq!(error("A + AB").message, "unknown name");
+        assert_eq!(error("A + AB").position, 4);
+        assert_eq!(error("SQRT(A)").message, "unknown name");
+        assert_eq!(error("SIGNED A").message, "expected '('");
+        assert_eq!(error("{A:8}").message, "expected a bit from 0 to 7");
+        assert_eq!(error("{AA:1}").message, "expected a byte");
+        assert_eq!(error("{A 1}").message, "expected ':'");
+        assert_eq!(error("{A:1").message, "expected '}'");
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let error = |formula: String| formula.parse::<Formula>().unwrap_err();
+        let nested = |depth: usize| format!("{}A{}", "(".repeat(depth), ")".repeat(depth));
+
+        assert!(nested(32).parse::<Formula>().is_ok());
+        assert_eq!(error(nested(33)).message, "nested too deeply");
+        assert_eq!(error(nested(33)).position, 33);
+        assert_eq!(
+            error(format!("{}A", "-".repeat(33))).message,
+            "nested too deeply"
+        );
+        assert_eq!(
+            error(format!("{}A{}", "SIGNED(".repeat(33), ")".repeat(33))).message,
+            "nested too deeply"
+        );
+        // Uploads can be a few kB, far deeper than the stack allows
+        assert_eq!(error(nested(2000)).message, "nested too deeply");
+
+        let chain = |operators: usize| format!("A{}", "+A".repeat(operators));
+        assert!(chain(64).parse::<Formula>().is_ok());
+        assert_eq!(error(chain(65)).message, "too many operators");
+    }
+}
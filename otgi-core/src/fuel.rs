@@ -0,0 +1,9 @@
+/// Stoichiometric air-fuel ratio of gasoline
+pub const STOICHIOMETRIC_AFR: f32 = 14.7;
+/// Density of gasoline in g/L
+pub const GASOLINE_DENSITY: f32 = 740.0;
+
+/// Fuel consumption in L/h from the mass air flow (g/s) and bank one fuel trims (%)
+pub fn fuel_rate(maf: f32, stft: f32, ltft: f32) -> f32 {
+    (maf * 3600.0) / ((STOICHIOMETRIC_AFR * (1.0 + ((stft + ltft) / 100.0))) * GASOLINE_DENSITY)
+}
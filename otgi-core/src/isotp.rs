@@ -0,0 +1,684 @@
+use crate::can::{CanFrame, CanId, CanTransport};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Largest payload a classic (12-bit length) First Frame can announce
+pub const MAX_PAYLOAD_LEN: usize = 0xFFF;
+
+/// Number of consecutive Wait flow control frames tolerated before giving up (N_WFTmax)
+const MAX_WAIT_FRAMES: u8 = 10;
+
+/// 11 bit IDs used for diagnostics, where requests and responses are 8 apart
+const DIAGNOSTIC_IDS: std::ops::RangeInclusive<u16> = 0x700..=0x7FF;
+/// PDU format of 29 bit physically addressed diagnostic messages (ISO 15765-4)
+const PHYSICAL_ADDRESSING: u32 = 0xDA;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IsoTpError<E> {
+    Transport(E),
+    Timeout,
+    InvalidFrame,
+    UnexpectedFrame,
+    SequenceMismatch { expected: u8, received: u8 },
+    Overflow,
+    PayloadTooLarge,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FlowStatus {
+    ContinueToSend = 0x0,
+    Wait = 0x1,
+    Overflow = 0x2,
+}
+
+/// A single CAN frame interpreted according to its protocol control information (PCI)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IsoTpFrame<'a> {
+    Single {
+        data: &'a [u8],
+    },
+    First {
+        len: usize,
+        data: &'a [u8],
+    },
+    Consecutive {
+        sequence: u8,
+        data: &'a [u8],
+    },
+    FlowControl {
+        status: FlowStatus,
+        block_size: u8,
+        separation_time: u8,
+    },
+}
+
+impl<'a> IsoTpFrame<'a> {
+    pub fn parse<E>(frame: &'a [u8]) -> Result<Self, IsoTpError<E>> {
+        let pci = *frame.first().ok_or(IsoTpError::InvalidFrame)?;
+
+        match pci >> 4 {
+            0x0 => {
+                let len = (pci & 0x0F) as usize;
+                if len == 0 || frame.len() < 1 + len {
+                    return Err(IsoTpError::InvalidFrame);
+                }
+                Ok(Self::Single {
+                    data: &frame[1..1 + len],
+                })
+            }
+            0x1 => {
+                if frame.len() < 2 {
+                    return Err(IsoTpError::InvalidFrame);
+                }
+                let len = (((pci & 0x0F) as usize) << 8) | frame[1] as usize;
+                // Anything that fits in a single frame must not be sent as a first frame
+                if len < 8 {
+                    return Err(IsoTpError::InvalidFrame);
+                }
+                Ok(Self::First {
+                    len,
+                    data: &frame[2..],
+                })
+            }
+            0x2 => Ok(Self::Consecutive {
+                sequence: pci & 0x0F,
+                data: &frame[1..],
+            }),
+            0x3 => {
+                if frame.len() < 3 {
+                    return Err(IsoTpError::InvalidFrame);
+                }
+                let status = match pci & 0x0F {
+                    0x0 => FlowStatus::ContinueToSend,
+                    0x1 => FlowStatus::Wait,
+                    0x2 => FlowStatus::Overflow,
+                    _ => return Err(IsoTpError::InvalidFrame),
+                };
+                Ok(Self::FlowControl {
+                    status,
+                    block_size: frame[1],
+                    separation_time: frame[2],
+                })
+            }
+            _ => Err(IsoTpError::InvalidFrame),
+        }
+    }
+
+    /// Encodes the frame into a full 8 byte CAN payload, padding unused bytes
+    pub fn encode(&self, padding: u8) -> [u8; 8] {
+        let mut msg = [padding; 8];
+        match self {
+            Self::Single { data } => {
+                msg[0] = data.len() as u8;
+                msg[1..1 + data.len()].copy_from_slice(data);
+            }
+            Self::First { len, data } => {
+                msg[0] = 0x10 | ((len >> 8) as u8 & 0x0F);
+                msg[1] = *len as u8;
+                msg[2..2 + data.len()].copy_from_slice(data);
+            }
+            Self::Consecutive { sequence, data } => {
+                msg[0] = 0x20 | (sequence & 0x0F);
+                msg[1..1 + data.len()].copy_from_slice(data);
+            }
+            Self::FlowControl {
+                status,
+                block_size,
+                separation_time,
+            } => {
+                msg[0] = 0x30 | *status as u8;
+                msg[1] = *block_size;
+                msg[2] = *separation_time;
+            }
+        }
+        msg
+    }
+}
+
+/// Converts an STmin byte into the delay it represents (ISO 15765-2 table 20)
+pub fn separation_time(raw: u8) -> Duration {
+    match raw {
+        0x00..=0x7F => Duration::from_millis(raw as u64),
+        0xF1..=0xF9 => Duration::from_micros((raw - 0xF0) as u64 * 100),
+        // Reserved values must be treated as the longest valid separation time
+        _ => Duration::from_millis(0x7F),
+    }
+}
+
+/// Legislated OBD ECUs answer on their physical request ID + 8 (e.g. 0x7E8 answers 0x7E0), or
+/// with the target and source addresses swapped for 29 bit IDs (0x18DAF110 answers 0x18DA10F1).
+/// `None` for IDs outside the 0x700-0x7FF diagnostic range or that aren't 29 bit physical
+/// addresses.
+pub fn physical_request_id(response_id: CanId) -> Option<CanId> {
+    match response_id {
+        CanId::Standard(id) => id
+            .checked_sub(8)
+            .filter(|id| DIAGNOSTIC_IDS.contains(id))
+            .map(CanId::Standard),
+        CanId::Extended(id) if (id >> 16) & 0xFF == PHYSICAL_ADDRESSING => Some(CanId::Extended(
+            (id & 0x1FFF_0000) | (id & 0xFF) << 8 | (id >> 8) & 0xFF,
+        )),
+        CanId::Extended(_) => None,
+    }
+}
+
+/// The ID an ECU answers requests sent to `request_id` on, see [`physical_request_id`]
+pub fn physical_response_id(request_id: CanId) -> Option<CanId> {
+    match request_id {
+        CanId::Standard(id) => id
+            .checked_add(8)
+            .filter(|id| DIAGNOSTIC_IDS.contains(id))
+            .map(CanId::Standard),
+        CanId::Extended(_) => physical_request_id(request_id),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IsoTpConfig {
+    /// Block size advertised in our flow control frames; 0 means send everything at once
+    pub block_size: u8,
+    /// STmin advertised in our flow control frames (raw encoding)
+    pub separation_time: u8,
+    /// Maximum time to wait for the next frame of a transfer (N_Bs / N_Cr)
+    pub timeout: Duration,
+    /// Maximum time to wait for a frame to be accepted for transmission (N_As)
+    pub transmit_timeout: Duration,
+    pub padding: u8,
+}
+
+impl Default for IsoTpConfig {
+    fn default() -> Self {
+        Self {
+            block_size: 0,
+            separation_time: 0,
+            timeout: Duration::from_millis(1000),
+            transmit_timeout: Duration::from_millis(100),
+            padding: 0x00,
+        }
+    }
+}
+
+fn transmit_frame<T: CanTransport>(
+    transport: &mut T,
+    config: &IsoTpConfig,
+    id: CanId,
+    frame: IsoTpFrame,
+) -> Result<(), IsoTpError<T::Error>> {
+    let tx_frame = CanFrame::new(id, &frame.encode(config.padding)).unwrap();
+
+    transport
+        .transmit(&tx_frame, config.transmit_timeout)
+        .map_err(IsoTpError::Transport)
+}
+
+/// Waits until `deadline` for a frame, optionally only accepting frames from `from`
+fn receive_frame<T: CanTransport>(
+    transport: &mut T,
+    from: Option<CanId>,
+    deadline: Instant,
+) -> Result<CanFrame, IsoTpError<T::Error>> {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(IsoTpError::Timeout);
+        }
+
+        let frame = transport
+            .receive(remaining)
+            .map_err(IsoTpError::Transport)?
+            .ok_or(IsoTpError::Timeout)?;
+
+        match from {
+            Some(id) if frame.id() != id => {
+                log::warn!(
+                    "Ignoring frame from {:#X} during transfer with {:#X}",
+                    frame.id().raw(),
+                    id.raw()
+                );
+            }
+            _ => return Ok(frame),
+        }
+    }
+}
+
+/// Sends `payload` to `id`, segmenting it and following the receiver's flow control if it does
+/// not fit in a single frame
+pub fn transmit<T: CanTransport>(
+    transport: &mut T,
+    config: &IsoTpConfig,
+    id: CanId,
+    payload: &[u8],
+) -> Result<(), IsoTpError<T::Error>> {
+    if payload.len() <= 7 {
+        return transmit_frame(transport, config, id, IsoTpFrame::Single { data: payload });
+    } else if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(IsoTpError::PayloadTooLarge);
+    }
+
+    transmit_frame(
+        transport,
+        config,
+        id,
+        IsoTpFrame::First {
+            len: payload.len(),
+            data: &payload[..6],
+        },
+    )?;
+
+    let mut chunks = payload[6..].chunks(7);
+    let mut sequence: u8 = 1;
+    let mut wait_frames = 0;
+
+    // Flow control from any other ECU is meant for someone else's transfer
+    let responder = physical_response_id(id);
+    loop {
+        let rx_frame = receive_frame(transport, responder, Instant::now() + config.timeout)?;
+        let (block_size, separation) = match IsoTpFrame::parse(rx_frame.data())? {
+            IsoTpFrame::FlowControl {
+                status: FlowStatus::ContinueToSend,
+                block_size,
+                separation_time: st,
+            } => (block_size, separation_time(st)),
+            IsoTpFrame::FlowControl {
+                status: FlowStatus::Wait,
+                ..
+            } => {
+                wait_frames += 1;
+                if wait_frames > MAX_WAIT_FRAMES {
+                    return Err(IsoTpError::Timeout);
+                }
+                continue;
+            }
+            IsoTpFrame::FlowControl {
+                status: FlowStatus::Overflow,
+                ..
+            } => return Err(IsoTpError::Overflow),
+            _ => return Err(IsoTpError::UnexpectedFrame),
+        };
+        wait_frames = 0;
+
+        let mut sent = 0;
+        while block_size == 0 || sent < block_size {
+            let Some(data) = chunks.next() else {
+                return Ok(());
+            };
+
+            if sent > 0 {
+                std::thread::sleep(separation);
+            }
+            transmit_frame(
+                transport,
+                config,
+                id,
+                IsoTpFrame::Consecutive { sequence, data },
+            )?;
+
+            sequence = (sequence + 1) & 0x0F;
+            sent += 1;
+        }
+
+        if chunks.len() == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Receives a complete payload, reassembling consecutive frames and sending flow control to
+/// the responder when required. Returns the ID of the responding ECU alongside the payload.
+pub fn receive<T: CanTransport>(
+    transport: &mut T,
+    config: &IsoTpConfig,
+    timeout: Duration,
+) -> Result<(CanId, Vec<u8>), IsoTpError<T::Error>> {
+    Receiver::default().receive(transport, config, Instant::now() + timeout)
+}
+
+/// A reassembled payload along with the ID it came from
+type Message = (CanId, Vec<u8>);
+
+/// A transfer that has started with a First Frame
+#[derive(Debug)]
+struct Transfer {
+    len: usize,
+    payload: Vec<u8>,
+    expected: u8,
+    in_block: u8,
+    flow_control_id: CanId,
+    /// When the next consecutive frame is due (N_Cr)
+    deadline: Instant,
+}
+
+/// Reassembles transfers from several responders at once, such as every ECU answering a
+/// functionally addressed request, each with its own sequence numbers and flow control
+#[derive(Debug, Default)]
+pub struct Receiver {
+    transfers: HashMap<CanId, Transfer>,
+}
+
+impl Receiver {
+    /// Forgets every transfer in progress, e.g. before sending a new request
+    pub fn clear(&mut self) {
+        self.transfers.clear();
+    }
+
+    /// Receives the next complete payload from any responder. New transfers may start until
+    /// `deadline`, and the ones in progress are waited for as long as their frames keep coming.
+    /// An error ends only the transfer of the responder that caused it.
+    pub fn receive<T: CanTransport>(
+        &mut self,
+        transport: &mut T,
+        config: &IsoTpConfig,
+        deadline: Instant,
+    ) -> Result<(CanId, Vec<u8>), IsoTpError<T::Error>> {
+        loop {
+            let now = Instant::now();
+            self.transfers.retain(|responder, transfer| {
+                let alive = transfer.deadline > now;
+                if !alive {
+                    log::warn!("Transfer from {:#X} stalled", responder.raw());
+                }
+                alive
+            });
+
+            let until = self
+                .transfers
+                .values()
+                .map(|transfer| transfer.deadline)
+                .fold(deadline, Instant::max);
+            let remaining = until.saturating_duration_since(now);
+            if remaining.is_zero() {
+                return Err(IsoTpError::Timeout);
+            }
+
+            let Some(frame) = transport
+                .receive(remaining)
+                .map_err(IsoTpError::Transport)?
+            else {
+                self.transfers.clear();
+                return Err(IsoTpError::Timeout);
+            };
+            if let Some(response) = self.accept(transport, config, &frame)? {
+                return Ok(response);
+            }
+        }
+    }
+
+    /// Adds `frame` to its responder's transfer, returning the payload once it's complete
+    fn accept<T: CanTransport>(
+        &mut self,
+        transport: &mut T,
+        config: &IsoTpConfig,
+        frame: &CanFrame,
+    ) -> Result<Option<Message>, IsoTpError<T::Error>> {
+        let responder = frame.id();
+        let parsed = IsoTpFrame::parse(frame.data()).inspect_err(|_| {
+            self.transfers.remove(&responder);
+        })?;
+
+        let flow_control = IsoTpFrame::FlowControl {
+            status: FlowStatus::ContinueToSend,
+            block_size: config.block_size,
+            separation_time: config.separation_time,
+        };
+        match parsed {
+            IsoTpFrame::Single { data } => {
+                if self.transfers.remove(&responder).is_some() {
+                    log::warn!("{:#X} abandoned its transfer", responder.raw());
+                }
+                Ok(Some((responder, data.to_vec())))
+            }
+            IsoTpFrame::First { len, data } => {
+                if self.transfers.remove(&responder).is_some() {
+                    log::warn!("{:#X} restarted its transfer", responder.raw());
+                }
+                // Nothing can be sent to a responder outside the physical addressing scheme
+                let flow_control_id =
+                    physical_request_id(responder).ok_or(IsoTpError::UnexpectedFrame)?;
+                transmit_frame(transport, config, flow_control_id, flow_control)?;
+
+                let mut payload = Vec::with_capacity(len);
+                payload.extend_from_slice(data);
+                self.transfers.insert(
+                    responder,
+                    Transfer {
+                        len,
+                        payload,
+                        expected: 1,
+                        in_block: 0,
+                        flow_control_id,
+                        deadline: Instant::now() + config.timeout,
+                    },
+                );
+                Ok(None)
+            }
+            IsoTpFrame::Consecutive { sequence, data } => {
+                let Some(transfer) = self.transfers.get_mut(&responder) else {
+                    // Left over from an earlier transfer
+                    log::warn!(
+                        "Ignoring {:02X?} from {:#X} outside of a transfer",
+                        frame.data(),
+                        responder.raw()
+                    );
+                    return Ok(None);
+                };
+
+                if sequence != transfer.expected {
+                    let expected = transfer.expected;
+                    self.transfers.remove(&responder);
+                    return Err(IsoTpError::SequenceMismatch {
+                        expected,
+                        received: sequence,
+                    });
+                }
+
+                let remaining = transfer.len - transfer.payload.len();
+                transfer
+                    .payload
+                    .extend_from_slice(&data[..remaining.min(data.len())]);
+                transfer.expected = (transfer.expected + 1) & 0x0F;
+                transfer.deadline = Instant::now() + config.timeout;
+                if transfer.payload.len() == transfer.len {
+                    let transfer = self.transfers.remove(&responder).unwrap();
+                    return Ok(Some((responder, transfer.payload)));
+                }
+
+                transfer.in_block += 1;
+                if config.block_size != 0 && transfer.in_block == config.block_size {
+                    transfer.in_block = 0;
+                    let flow_control_id = transfer.flow_control_id;
+                    transmit_frame(transport, config, flow_control_id, flow_control)?;
+                }
+                Ok(None)
+            }
+            // Meant for someone else's transfer
+            IsoTpFrame::FlowControl { .. } => {
+                log::warn!(
+                    "Ignoring {:02X?} from {:#X} outside of a transfer",
+                    frame.data(),
+                    responder.raw()
+                );
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransport;
+
+    fn frame(id: u16, data: &[u8]) -> CanFrame {
+        CanFrame::new(CanId::Standard(id), data).unwrap()
+    }
+
+    #[test]
+    fn parses_each_frame_type() {
+        assert_eq!(
+            IsoTpFrame::parse::<()>(&[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0]),
+            Ok(IsoTpFrame::Single {
+                data: &[0x41, 0x0D, 0x32]
+            })
+        );
+        assert_eq!(
+            IsoTpFrame::parse::<()>(&[0x10, 0x14, 0x49, 0x02, 0x01, 0x31, 0x44, 0x34]),
+            Ok(IsoTpFrame::First {
+                len: 20,
+                data: &[0x49, 0x02, 0x01, 0x31, 0x44, 0x34]
+            })
+        );
+        assert_eq!(
+            IsoTpFrame::parse::<()>(&[0x21, 1, 2, 3, 4, 5, 6, 7]),
+            Ok(IsoTpFrame::Consecutive {
+                sequence: 1,
+                data: &[1, 2, 3, 4, 5, 6, 7]
+            })
+        );
+        assert_eq!(
+            IsoTpFrame::parse::<()>(&[0x30, 0x08, 0xF5, 0, 0, 0, 0, 0]),
+            Ok(IsoTpFrame::FlowControl {
+                status: FlowStatus::ContinueToSend,
+                block_size: 8,
+                separation_time: 0xF5
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_frames() {
+        assert_eq!(IsoTpFrame::parse::<()>(&[]), Err(IsoTpError::InvalidFrame));
+        assert_eq!(
+            IsoTpFrame::parse::<()>(&[0x00, 0, 0, 0, 0, 0, 0, 0]),
+            Err(IsoTpError::InvalidFrame)
+        );
+        assert_eq!(
+            IsoTpFrame::parse::<()>(&[0x05, 0x41, 0x0C]),
+            Err(IsoTpError::InvalidFrame)
+        );
+        assert_eq!(
+            IsoTpFrame::parse::<()>(&[0x10, 0x07, 0, 0, 0, 0, 0, 0]),
+            Err(IsoTpError::InvalidFrame)
+        );
+        assert_eq!(
+            IsoTpFrame::parse::<()>(&[0x33, 0, 0]),
+            Err(IsoTpError::InvalidFrame)
+        );
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        let frames = [
+            IsoTpFrame::Single {
+                data: &[0x01, 0x0C],
+            },
+            IsoTpFrame::First {
+                len: 0x123,
+                data: &[1, 2, 3, 4, 5, 6],
+            },
+            IsoTpFrame::Consecutive {
+                sequence: 0xF,
+                data: &[1, 2, 3, 4, 5, 6, 7],
+            },
+            IsoTpFrame::FlowControl {
+                status: FlowStatus::Wait,
+                block_size: 4,
+                separation_time: 10,
+            },
+        ];
+
+        for frame in frames {
+            let encoded = frame.encode(0xAA);
+            assert_eq!(IsoTpFrame::parse::<()>(&encoded), Ok(frame));
+        }
+    }
+
+    #[test]
+    fn separation_time_encoding() {
+        assert_eq!(separation_time(0x00), Duration::ZERO);
+        assert_eq!(separation_time(0x7F), Duration::from_millis(127));
+        assert_eq!(separation_time(0xF1), Duration::from_micros(100));
+        assert_eq!(separation_time(0xF9), Duration::from_micros(900));
+        assert_eq!(separation_time(0x80), Duration::from_millis(127));
+        assert_eq!(separation_time(0xFA), Duration::from_millis(127));
+    }
+
+    #[test]
+    fn physical_request_ids() {
+        assert_eq!(
+            physical_request_id(CanId::Standard(0x7E9)),
+            Some(CanId::Standard(0x7E1))
+        );
+        assert_eq!(
+            physical_request_id(CanId::Extended(0x18DA_F110)),
+            Some(CanId::Extended(0x18DA_10F1))
+        );
+        assert_eq!(
+            physical_response_id(CanId::Standard(0x7E1)),
+            Some(CanId::Standard(0x7E9))
+        );
+        assert_eq!(
+            physical_response_id(CanId::Extended(0x18DA_10F1)),
+            Some(CanId::Extended(0x18DA_F110))
+        );
+        assert_eq!(
+            physical_response_id(CanId::Standard(0x7C0)),
+            Some(CanId::Standard(0x7C8))
+        );
+
+        // Would underflow or leave the diagnostic range
+        assert_eq!(physical_request_id(CanId::Standard(0x003)), None);
+        assert_eq!(physical_request_id(CanId::Standard(0x123)), None);
+        assert_eq!(physical_request_id(CanId::Standard(0x704)), None);
+        assert_eq!(physical_response_id(CanId::Standard(0x7FA)), None);
+        // Functionally addressed and non-diagnostic 29 bit IDs
+        assert_eq!(physical_request_id(CanId::Extended(0x18DB_33F1)), None);
+        assert_eq!(physical_request_id(CanId::Extended(0x0CF0_0400)), None);
+    }
+
+    #[test]
+    fn skips_stray_frames_before_a_transfer() {
+        let mut transport = MockTransport::new();
+        transport
+            .push_rx(frame(0x7E9, &[0x21, 1, 2, 3, 4, 5, 6, 7]))
+            .push_rx(frame(0x7EA, &[0x30, 0x00, 0x00, 0, 0, 0, 0, 0]))
+            .push_rx(frame(0x7E8, &[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0]));
+
+        assert_eq!(
+            receive(
+                &mut transport,
+                &Default::default(),
+                Duration::from_millis(10)
+            ),
+            Ok((CanId::Standard(0x7E8), vec![0x41, 0x0D, 0x32]))
+        );
+    }
+
+    #[test]
+    fn only_follows_flow_control_from_the_receiver() {
+        let mut transport = MockTransport::new();
+        transport
+            .expect(
+                frame(0x7E0, &[0x10, 0x08, 0x2E, 0xF1, 0x90, 1, 2, 3]),
+                [
+                    // Another ECU telling its own sender to stop
+                    frame(0x7E9, &[0x32, 0x00, 0x00, 0, 0, 0, 0, 0]),
+                    frame(0x7E8, &[0x30, 0x00, 0x00, 0, 0, 0, 0, 0]),
+                ],
+            )
+            .expect(frame(0x7E0, &[0x21, 4, 5, 0, 0, 0, 0, 0]), []);
+
+        assert_eq!(
+            transmit(
+                &mut transport,
+                &Default::default(),
+                CanId::Standard(0x7E0),
+                &[0x2E, 0xF1, 0x90, 1, 2, 3, 4, 5]
+            ),
+            Ok(())
+        );
+        assert!(transport.is_done());
+    }
+}
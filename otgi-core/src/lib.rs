@@ -0,0 +1,19 @@
+#![allow(clippy::uninlined_format_args)]
+
+pub mod can;
+pub mod custom_pid;
+pub mod dtc;
+pub mod formula;
+pub mod fuel;
+pub mod isotp;
+pub mod mock;
+pub mod obd;
+pub mod readiness;
+pub mod scheduler;
+pub mod sim;
+pub mod sniffer;
+#[cfg(all(feature = "socketcan", target_os = "linux"))]
+pub mod socketcan;
+pub mod uds;
+pub mod units;
+pub mod vehicle_info;
@@ -0,0 +1,128 @@
+use crate::can::{BusState, BusStatus, CanFilter, CanFrame, CanTransport};
+use std::{collections::VecDeque, time::Duration};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockError {
+    /// A frame was transmitted that didn't match the next scripted request
+    UnexpectedFrame {
+        expected: Option<CanFrame>,
+        received: CanFrame,
+    },
+    BusOff,
+}
+
+#[derive(Debug, Clone)]
+struct Expectation {
+    request: CanFrame,
+    responses: Vec<CanFrame>,
+}
+
+/// An in-memory bus that checks transmitted frames against a script and answers with the
+/// scripted responses. Receiving with nothing queued behaves like a timeout without sleeping.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    expectations: VecDeque<Expectation>,
+    rx: VecDeque<CanFrame>,
+    sent: Vec<CanFrame>,
+    filter: CanFilter,
+    bus_off: bool,
+    recoveries: u32,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Expects `request` to be the next transmitted frame and queues `responses` once it is
+    pub fn expect(
+        &mut self,
+        request: CanFrame,
+        responses: impl IntoIterator<Item = CanFrame>,
+    ) -> &mut Self {
+        self.expectations.push_back(Expectation {
+            request,
+            responses: responses.into_iter().collect(),
+        });
+        self
+    }
+
+    /// Queues a frame as if it were received without being requested
+    pub fn push_rx(&mut self, frame: CanFrame) -> &mut Self {
+        self.rx.push_back(frame);
+        self
+    }
+
+    /// Fails every transmission until [`CanTransport::recover`] is called
+    pub fn go_bus_off(&mut self) -> &mut Self {
+        self.bus_off = true;
+        self
+    }
+
+    pub fn recoveries(&self) -> u32 {
+        self.recoveries
+    }
+
+    pub fn sent(&self) -> &[CanFrame] {
+        &self.sent
+    }
+
+    /// Whether every scripted request has been transmitted
+    pub fn is_done(&self) -> bool {
+        self.expectations.is_empty()
+    }
+}
+
+impl CanTransport for MockTransport {
+    type Error = MockError;
+
+    fn transmit(&mut self, frame: &CanFrame, _timeout: Duration) -> Result<(), Self::Error> {
+        if self.bus_off {
+            return Err(MockError::BusOff);
+        }
+        self.sent.push(*frame);
+
+        match self.expectations.pop_front() {
+            Some(expectation) if expectation.request == *frame => {
+                self.rx.extend(expectation.responses);
+                Ok(())
+            }
+            expectation => Err(MockError::UnexpectedFrame {
+                expected: expectation.map(|e| e.request),
+                received: *frame,
+            }),
+        }
+    }
+
+    fn receive(&mut self, _timeout: Duration) -> Result<Option<CanFrame>, Self::Error> {
+        while let Some(frame) = self.rx.pop_front() {
+            if self.filter.matches(frame.id()) {
+                return Ok(Some(frame));
+            }
+        }
+        Ok(None)
+    }
+
+    fn set_filter(&mut self, filter: CanFilter) -> Result<(), Self::Error> {
+        self.filter = filter;
+        Ok(())
+    }
+
+    fn status(&mut self) -> Result<BusStatus, Self::Error> {
+        Ok(BusStatus {
+            state: if self.bus_off {
+                BusState::BusOff
+            } else {
+                BusState::Running
+            },
+            tx_error_counter: if self.bus_off { 256 } else { 0 },
+            rx_error_counter: 0,
+        })
+    }
+
+    fn recover(&mut self) -> Result<(), Self::Error> {
+        self.bus_off = false;
+        self.recoveries += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,1988 @@
+use crate::{
+    can::{BusState, BusStatus, CanFilter, CanFrame, CanId, CanTransport},
+    custom_pid::CustomPid,
+    dtc::DiagnosticTroubleCode,
+    formula::Formula,
+    isotp,
+    readiness::MonitorStatus,
+    uds,
+    units::{Quantity, Unit},
+    vehicle_info::{InfoType, VehicleInfo},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+use strum::IntoEnumIterator;
+
+struct ObdResponse<'a> {
+    mode: ObdMode,
+    format: Option<&'a PID>,
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum ObdReadableData {
+    Value(Quantity),
+    DTC(Vec<DiagnosticTroubleCode>),
+    PendingDTC(Vec<DiagnosticTroubleCode>),
+    PermanentDTC(Vec<DiagnosticTroubleCode>),
+    MonitorStatus(MonitorStatus),
+    SupportedPids(Vec<u8>),
+    /// Answers to a query for several PIDs, see [`ObdQuery::multiple`]
+    Values(HashMap<PID, ObdReadableData>),
+    Unknown(Vec<u8>),
+}
+
+impl ObdReadableData {
+    /// The value converted to `unit`, or `None` if this is not a value of that quantity
+    pub fn value_in(&self, unit: Unit) -> Option<f32> {
+        match self {
+            ObdReadableData::Value(quantity) => quantity.to(unit),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes a mode 03, 07 or 0A response; the count byte is followed by two bytes per code and
+/// 0000 pads unused slots
+fn decode_dtcs(data: &[u8]) -> Result<Vec<DiagnosticTroubleCode>, ()> {
+    let (&count, codes) = data.split_first().ok_or(())?;
+    if codes.len() < 2 * count as usize {
+        return Err(());
+    }
+
+    Ok(codes
+        .chunks_exact(2)
+        .take(count as usize)
+        .filter(|code| code != &[0x00, 0x00])
+        .map(|code| DiagnosticTroubleCode::from_bytes([code[0], code[1]]))
+        .collect())
+}
+
+/// Splits an interleaved mode 01 response (`<PID> <data> <PID> <data> ...`) by PID; ECUs leave
+/// out the PIDs they don't support
+fn decode_pids(requested: &[PID], mut data: &[u8]) -> Result<HashMap<PID, ObdReadableData>, ()> {
+    let mut values = HashMap::new();
+    while let Some((&raw, rest)) = data.split_first() {
+        let pid = *requested.iter().find(|&&pid| pid as u8 == raw).ok_or(())?;
+        let len = pid.data_len();
+        if rest.len() < len {
+            return Err(());
+        }
+
+        let response = ObdResponse {
+            mode: ObdMode::QueryNow,
+            format: Some(&pid),
+            data: &rest[..len],
+        };
+        values.insert(pid, ObdReadableData::try_from(response)?);
+        data = &rest[len..];
+    }
+    Ok(values)
+}
+
+/// Every [`PID::formula`], parsed on first use
+fn formulas() -> &'static HashMap<PID, Formula> {
+    static FORMULAS: OnceLock<HashMap<PID, Formula>> = OnceLock::new();
+    FORMULAS.get_or_init(|| {
+        PID::iter()
+            .filter_map(|pid| Some((pid, pid.formula()?.parse().unwrap())))
+            .collect()
+    })
+}
+
+impl<'a> TryFrom<ObdResponse<'a>> for ObdReadableData {
+    type Error = ();
+    fn try_from(response: ObdResponse) -> Result<Self, Self::Error> {
+        match response.mode {
+            ObdMode::QueryDTC => Ok(ObdReadableData::DTC(decode_dtcs(response.data)?)),
+            ObdMode::QueryPendingDTC => {
+                Ok(ObdReadableData::PendingDTC(decode_dtcs(response.data)?))
+            }
+            ObdMode::QueryPermanentDTC => {
+                Ok(ObdReadableData::PermanentDTC(decode_dtcs(response.data)?))
+            }
+            // Freeze frames hold the same PIDs as the live data
+            ObdMode::QueryNow | ObdMode::QueryFreezeFrame => {
+                let pid = response.format.expect("no format on query");
+                let value = match pid {
+                    pid if pid.is_capability() => {
+                        if response.data.len() < 4 {
+                            return Err(());
+                        }
+                        let bitmap = u32::from_be_bytes(response.data[..4].try_into().unwrap());
+                        return Ok(ObdReadableData::SupportedPids(
+                            (1..=0x20)
+                                .filter(|i| bitmap & (1 << (0x20 - i)) != 0)
+                                .filter_map(|i| (*pid as u8).checked_add(i))
+                                .collect(),
+                        ));
+                    } // bit 31 of ABCD is the PID after the capability PID, bit 0 the next capability
+                    PID::MonitorStatus => {
+                        return Ok(ObdReadableData::MonitorStatus(MonitorStatus::decode(
+                            response.data,
+                        )?));
+                    }
+                    PID::FreezeFrameDtc => {
+                        if response.data.len() < 2 {
+                            return Err(());
+                        }
+                        // 0000 means no freeze frame is stored
+                        let code = [response.data[0], response.data[1]];
+                        return Ok(ObdReadableData::DTC(
+                            Some(code)
+                                .filter(|code| code != &[0x00, 0x00])
+                                .map(DiagnosticTroubleCode::from_bytes)
+                                .into_iter()
+                                .collect(),
+                        ));
+                    }
+                    pid => {
+                        if response.data.len() < pid.data_len() {
+                            return Err(());
+                        }
+                        formulas()
+                            .get(pid)
+                            .ok_or(())?
+                            .eval(response.data)
+                            .ok_or(())?
+                    }
+                };
+
+                Ok(ObdReadableData::Value(Quantity::new(value, pid.unit())))
+            }
+            _ => Ok(ObdReadableData::Unknown(response.data.to_vec())),
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(
+    strum::FromRepr, strum::EnumString, strum::EnumIter, Copy, Clone, Debug, PartialEq, Eq, Hash,
+)]
+pub enum PID {
+    FirstCap = 0x00,
+    /// MIL, DTC count and readiness monitors
+    MonitorStatus = 0x01,
+    /// Mode 02 only: the DTC that caused the freeze frame to be stored
+    FreezeFrameDtc = 0x02,
+    EngineSpeed = 0x0C,
+    VehicleSpeed = 0x0D,
+    ThrottlePosition = 0x11,
+    RunTime = 0x1F,
+    SecondCap = 0x20,
+    FuelTankLevelInput = 0x2F,
+    RelativeThrottlePosition = 0x45,
+    EngineFuelRate = 0x5E,
+    Odometer = 0xA6,
+    MassAirFlow = 0x10,
+    ShortTermFuelTrimBankOne = 0x06,
+    LongTermFuelTrimBankOne = 0x07,
+    ThirdCap = 0x40,
+    FourthCap = 0x60,
+    FifthCap = 0x80,
+    SixthCap = 0xA0,
+    SeventhCap = 0xC0,
+    EighthCap = 0xE0,
+    CalculatedEngineLoad = 0x04,
+    EngineCoolantTemperature = 0x05,
+    ShortTermFuelTrimBankTwo = 0x08,
+    LongTermFuelTrimBankTwo = 0x09,
+    FuelPressure = 0x0A,
+    IntakeManifoldAbsolutePressure = 0x0B,
+    TimingAdvance = 0x0E,
+    IntakeAirTemperature = 0x0F,
+    OxygenSensorOneVoltage = 0x14,
+    OxygenSensorTwoVoltage = 0x15,
+    OxygenSensorThreeVoltage = 0x16,
+    OxygenSensorFourVoltage = 0x17,
+    OxygenSensorFiveVoltage = 0x18,
+    OxygenSensorSixVoltage = 0x19,
+    OxygenSensorSevenVoltage = 0x1A,
+    OxygenSensorEightVoltage = 0x1B,
+    DistanceWithMilOn = 0x21,
+    FuelRailPressure = 0x22,
+    FuelRailGaugePressure = 0x23,
+    WideRangeOxygenSensorOne = 0x24,
+    WideRangeOxygenSensorTwo = 0x25,
+    WideRangeOxygenSensorThree = 0x26,
+    WideRangeOxygenSensorFour = 0x27,
+    WideRangeOxygenSensorFive = 0x28,
+    WideRangeOxygenSensorSix = 0x29,
+    WideRangeOxygenSensorSeven = 0x2A,
+    WideRangeOxygenSensorEight = 0x2B,
+    CommandedEgr = 0x2C,
+    EgrError = 0x2D,
+    CommandedEvaporativePurge = 0x2E,
+    WarmUpsSinceCodesCleared = 0x30,
+    DistanceSinceCodesCleared = 0x31,
+    EvapSystemVaporPressure = 0x32,
+    AbsoluteBarometricPressure = 0x33,
+    CatalystTemperatureBankOneSensorOne = 0x3C,
+    CatalystTemperatureBankTwoSensorOne = 0x3D,
+    CatalystTemperatureBankOneSensorTwo = 0x3E,
+    CatalystTemperatureBankTwoSensorTwo = 0x3F,
+    ControlModuleVoltage = 0x42,
+    AbsoluteLoadValue = 0x43,
+    CommandedAirFuelEquivalenceRatio = 0x44,
+    AmbientAirTemperature = 0x46,
+    AbsoluteThrottlePositionB = 0x47,
+    AbsoluteThrottlePositionC = 0x48,
+    AcceleratorPedalPositionD = 0x49,
+    AcceleratorPedalPositionE = 0x4A,
+    AcceleratorPedalPositionF = 0x4B,
+    CommandedThrottleActuator = 0x4C,
+    TimeRunWithMilOn = 0x4D,
+    TimeSinceCodesCleared = 0x4E,
+    MaximumMassAirFlow = 0x50,
+    EthanolFuelPercentage = 0x52,
+    AbsoluteEvapSystemVaporPressure = 0x53,
+    WideEvapSystemVaporPressure = 0x54,
+    FuelRailAbsolutePressure = 0x59,
+    RelativeAcceleratorPedalPosition = 0x5A,
+    HybridBatteryRemainingLife = 0x5B,
+    EngineOilTemperature = 0x5C,
+    FuelInjectionTiming = 0x5D,
+    DriverDemandEngineTorque = 0x61,
+    ActualEngineTorque = 0x62,
+    EngineReferenceTorque = 0x63,
+    // TODO: bit-encoded PIDs (fuel system status, O2 sensors present, fuel type, ...)
+}
+
+impl PID {
+    /// Whether this PID is one of the bitmaps of supported PIDs (0x00, 0x20, ... 0xE0)
+    pub fn is_capability(&self) -> bool {
+        *self as u8 % 0x20 == 0
+    }
+
+    /// Whether this PID decodes to a [`Quantity`] rather than a bitmap, code or status
+    pub fn has_value(&self) -> bool {
+        !self.is_capability() && !matches!(self, PID::MonitorStatus | PID::FreezeFrameDtc)
+    }
+
+    /// Number of data bytes following this PID in a response
+    pub fn data_len(&self) -> usize {
+        match self {
+            pid if pid.is_capability() => 4,
+            PID::MonitorStatus
+            | PID::Odometer
+            | PID::MaximumMassAirFlow
+            | PID::WideRangeOxygenSensorOne
+            | PID::WideRangeOxygenSensorTwo
+            | PID::WideRangeOxygenSensorThree
+            | PID::WideRangeOxygenSensorFour
+            | PID::WideRangeOxygenSensorFive
+            | PID::WideRangeOxygenSensorSix
+            | PID::WideRangeOxygenSensorSeven
+            | PID::WideRangeOxygenSensorEight => 4,
+            PID::FreezeFrameDtc
+            | PID::EngineSpeed
+            | PID::MassAirFlow
+            | PID::EngineFuelRate
+            | PID::RunTime
+            | PID::DistanceWithMilOn
+            | PID::DistanceSinceCodesCleared
+            | PID::TimeRunWithMilOn
+            | PID::TimeSinceCodesCleared
+            | PID::EngineReferenceTorque
+            | PID::OxygenSensorOneVoltage
+            | PID::OxygenSensorTwoVoltage
+            | PID::OxygenSensorThreeVoltage
+            | PID::OxygenSensorFourVoltage
+            | PID::OxygenSensorFiveVoltage
+            | PID::OxygenSensorSixVoltage
+            | PID::OxygenSensorSevenVoltage
+            | PID::OxygenSensorEightVoltage
+            | PID::FuelRailPressure
+            | PID::FuelRailGaugePressure
+            | PID::FuelRailAbsolutePressure
+            | PID::CommandedAirFuelEquivalenceRatio
+            | PID::EvapSystemVaporPressure
+            | PID::CatalystTemperatureBankOneSensorOne
+            | PID::CatalystTemperatureBankTwoSensorOne
+            | PID::CatalystTemperatureBankOneSensorTwo
+            | PID::CatalystTemperatureBankTwoSensorTwo
+            | PID::ControlModuleVoltage
+            | PID::AbsoluteLoadValue
+            | PID::AbsoluteEvapSystemVaporPressure
+            | PID::WideEvapSystemVaporPressure
+            | PID::FuelInjectionTiming => 2,
+            _ => 1,
+        }
+    }
+
+    /// How the value is computed from the data bytes, see [`Formula`]. Where a PID returns more
+    /// bytes than its formula reads, only the first value is kept.
+    pub fn formula(&self) -> Option<&'static str> {
+        Some(match self {
+            PID::MassAirFlow => "(256*A+B)/100",
+            PID::EngineFuelRate => "(256*A+B)/20",
+            PID::EngineSpeed => "(256*A+B)/4",
+            PID::RunTime
+            | PID::DistanceWithMilOn
+            | PID::DistanceSinceCodesCleared
+            | PID::TimeRunWithMilOn
+            | PID::TimeSinceCodesCleared
+            | PID::EngineReferenceTorque => "256*A+B",
+            PID::VehicleSpeed
+            | PID::IntakeManifoldAbsolutePressure
+            | PID::WarmUpsSinceCodesCleared
+            | PID::AbsoluteBarometricPressure => "A",
+            PID::ThrottlePosition
+            | PID::FuelTankLevelInput
+            | PID::RelativeThrottlePosition
+            | PID::CalculatedEngineLoad
+            | PID::CommandedEgr
+            | PID::CommandedEvaporativePurge
+            | PID::AbsoluteThrottlePositionB
+            | PID::AbsoluteThrottlePositionC
+            | PID::AcceleratorPedalPositionD
+            | PID::AcceleratorPedalPositionE
+            | PID::AcceleratorPedalPositionF
+            | PID::CommandedThrottleActuator
+            | PID::EthanolFuelPercentage
+            | PID::RelativeAcceleratorPedalPosition
+            | PID::HybridBatteryRemainingLife => "A/2.55",
+            PID::Odometer => "(A*16777216+B*65536+C*256+D)/10",
+            PID::ShortTermFuelTrimBankOne
+            | PID::LongTermFuelTrimBankOne
+            | PID::ShortTermFuelTrimBankTwo
+            | PID::LongTermFuelTrimBankTwo
+            | PID::EgrError => "A/1.28-100",
+            PID::EngineCoolantTemperature
+            | PID::IntakeAirTemperature
+            | PID::AmbientAirTemperature
+            | PID::EngineOilTemperature => "A-40",
+            PID::FuelPressure => "3*A",
+            PID::TimingAdvance => "A/2-64",
+            // B is the sensor's fuel trim (0xFF when unused)
+            PID::OxygenSensorOneVoltage
+            | PID::OxygenSensorTwoVoltage
+            | PID::OxygenSensorThreeVoltage
+            | PID::OxygenSensorFourVoltage
+            | PID::OxygenSensorFiveVoltage
+            | PID::OxygenSensorSixVoltage
+            | PID::OxygenSensorSevenVoltage
+            | PID::OxygenSensorEightVoltage => "A/200",
+            PID::FuelRailPressure => "0.079*(256*A+B)",
+            PID::FuelRailGaugePressure | PID::FuelRailAbsolutePressure => "10*(256*A+B)",
+            // Wide range sensors report the voltage in CD
+            PID::WideRangeOxygenSensorOne
+            | PID::WideRangeOxygenSensorTwo
+            | PID::WideRangeOxygenSensorThree
+            | PID::WideRangeOxygenSensorFour
+            | PID::WideRangeOxygenSensorFive
+            | PID::WideRangeOxygenSensorSix
+            | PID::WideRangeOxygenSensorSeven
+            | PID::WideRangeOxygenSensorEight
+            | PID::CommandedAirFuelEquivalenceRatio => "(256*A+B)*2/65536",
+            PID::EvapSystemVaporPressure => "SIGNED16(256*A+B)/4",
+            PID::CatalystTemperatureBankOneSensorOne
+            | PID::CatalystTemperatureBankTwoSensorOne
+            | PID::CatalystTemperatureBankOneSensorTwo
+            | PID::CatalystTemperatureBankTwoSensorTwo => "(256*A+B)/10-40",
+            PID::ControlModuleVoltage => "(256*A+B)/1000",
+            PID::AbsoluteLoadValue => "(256*A+B)/2.55",
+            PID::MaximumMassAirFlow => "10*A",
+            PID::AbsoluteEvapSystemVaporPressure => "(256*A+B)/200",
+            PID::WideEvapSystemVaporPressure => "256*A+B-32767",
+            PID::FuelInjectionTiming => "(256*A+B)/128-210",
+            PID::DriverDemandEngineTorque | PID::ActualEngineTorque => "A-125",
+            _ => return None,
+        })
+    }
+
+    /// The unit the decoded value of this PID is measured in
+    pub fn unit(&self) -> Unit {
+        match self {
+            PID::EngineSpeed => Unit::Rpm,
+            PID::VehicleSpeed => Unit::KilometersPerHour,
+            PID::MassAirFlow | PID::MaximumMassAirFlow => Unit::GramsPerSecond,
+            PID::EngineFuelRate => Unit::LitersPerHour,
+            PID::RunTime => Unit::Seconds,
+            PID::TimeRunWithMilOn | PID::TimeSinceCodesCleared => Unit::Minutes,
+            PID::DistanceWithMilOn | PID::DistanceSinceCodesCleared | PID::Odometer => {
+                Unit::Kilometers
+            }
+            PID::EngineCoolantTemperature
+            | PID::IntakeAirTemperature
+            | PID::AmbientAirTemperature
+            | PID::EngineOilTemperature
+            | PID::CatalystTemperatureBankOneSensorOne
+            | PID::CatalystTemperatureBankTwoSensorOne
+            | PID::CatalystTemperatureBankOneSensorTwo
+            | PID::CatalystTemperatureBankTwoSensorTwo => Unit::Celsius,
+            PID::FuelPressure
+            | PID::IntakeManifoldAbsolutePressure
+            | PID::AbsoluteBarometricPressure
+            | PID::FuelRailPressure
+            | PID::FuelRailGaugePressure
+            | PID::FuelRailAbsolutePressure
+            | PID::AbsoluteEvapSystemVaporPressure => Unit::Kilopascals,
+            PID::EvapSystemVaporPressure | PID::WideEvapSystemVaporPressure => Unit::Pascals,
+            PID::TimingAdvance | PID::FuelInjectionTiming => Unit::Degrees,
+            PID::OxygenSensorOneVoltage
+            | PID::OxygenSensorTwoVoltage
+            | PID::OxygenSensorThreeVoltage
+            | PID::OxygenSensorFourVoltage
+            | PID::OxygenSensorFiveVoltage
+            | PID::OxygenSensorSixVoltage
+            | PID::OxygenSensorSevenVoltage
+            | PID::OxygenSensorEightVoltage
+            | PID::ControlModuleVoltage => Unit::Volts,
+            PID::WideRangeOxygenSensorOne
+            | PID::WideRangeOxygenSensorTwo
+            | PID::WideRangeOxygenSensorThree
+            | PID::WideRangeOxygenSensorFour
+            | PID::WideRangeOxygenSensorFive
+            | PID::WideRangeOxygenSensorSix
+            | PID::WideRangeOxygenSensorSeven
+            | PID::WideRangeOxygenSensorEight
+            | PID::CommandedAirFuelEquivalenceRatio => Unit::Ratio,
+            PID::EngineReferenceTorque => Unit::NewtonMeters,
+            PID::WarmUpsSinceCodesCleared => Unit::Count,
+            _ => Unit::Percent,
+        }
+    }
+}
+
+impl From<PID> for u8 {
+    fn from(pid: PID) -> Self {
+        pid as u8
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObdError<E> {
+    Transport(E),
+    IsoTp(isotp::IsoTpError<E>),
+    MalformedResponse,
+    /// The ECU answered `0x7F <service> <code>`, see [`NegativeResponseCode`]
+    NegativeResponse {
+        service: u8,
+        code: u8,
+    },
+    /// No ECU answered on any of the protocols tried by [`ObdDriver::detect`]
+    ProtocolNotDetected,
+    /// The ID doesn't follow the physical addressing scheme, so there's no ID to reach it on
+    UnknownEcu(CanId),
+}
+
+/// Common codes of `0x7F` negative responses (ISO 14229-1)
+#[repr(u8)]
+#[derive(strum::FromRepr, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NegativeResponseCode {
+    GeneralReject = 0x10,
+    ServiceNotSupported = 0x11,
+    SubFunctionNotSupported = 0x12,
+    IncorrectMessageLength = 0x13,
+    ResponseTooLong = 0x14,
+    BusyRepeatRequest = 0x21,
+    ConditionsNotCorrect = 0x22,
+    RequestSequenceError = 0x24,
+    RequestOutOfRange = 0x31,
+    SecurityAccessDenied = 0x33,
+    /// The request was received but the answer will take longer than the usual response window
+    ResponsePending = 0x78,
+    SubFunctionNotSupportedInActiveSession = 0x7E,
+    ServiceNotSupportedInActiveSession = 0x7F,
+}
+
+impl<E> From<isotp::IsoTpError<E>> for ObdError<E> {
+    fn from(err: isotp::IsoTpError<E>) -> Self {
+        match err {
+            isotp::IsoTpError::Transport(e) => Self::Transport(e),
+            e => Self::IsoTp(e),
+        }
+    }
+}
+
+#[derive(strum::FromRepr, Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ObdMode {
+    QueryNow = 0x01,
+    QueryFreezeFrame = 0x02,
+    QueryDTC = 0x03,
+    ClearDTC = 0x04,
+    QueryPendingDTC = 0x07,
+    VehicleInformation = 0x09,
+    QueryPermanentDTC = 0x0A,
+    // TODO: additional modes
+}
+
+struct ObdRequest<'a> {
+    mode: ObdMode,
+    data: &'a [PID],
+    /// Freeze frame number, only sent in mode 02
+    frame: u8,
+}
+
+impl<'a> ObdRequest<'a> {
+    /// Assembles the request payload; framing is left to the ISO-TP layer
+    fn assemble(&mut self) -> Result<Vec<u8>, ()> {
+        if matches!(
+            self.mode,
+            ObdMode::QueryDTC
+                | ObdMode::ClearDTC
+                | ObdMode::QueryPendingDTC
+                | ObdMode::QueryPermanentDTC
+        ) {
+            self.data = &[];
+        }
+
+        // A single mode 01 request may carry at most six PIDs
+        if self.data.len() > 6 {
+            return Err(());
+        }
+
+        let mut msg = Vec::with_capacity(1 + 2 * self.data.len());
+        msg.push(self.mode as u8);
+        for &pid in self.data {
+            msg.push(pid as u8);
+            if self.mode == ObdMode::QueryFreezeFrame {
+                msg.push(self.frame);
+            }
+        }
+
+        Ok(msg)
+    }
+}
+
+/// CAN identifiers used for OBD requests and responses (ISO 15765-4)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Addressing {
+    /// 11 bit: requests on 0x7DF or 0x7E0-0x7E7, responses on 0x7E8-0x7EF
+    #[default]
+    Standard,
+    /// 29 bit: requests on 0x18DB33F1 or 0x18DAxxF1, responses on 0x18DAF1xx
+    Extended,
+}
+
+impl Addressing {
+    /// The ID every ECU listens to
+    pub fn functional_request_id(&self) -> CanId {
+        match self {
+            Addressing::Standard => CanId::Standard(0x7DF),
+            Addressing::Extended => CanId::Extended(0x18DB_33F1),
+        }
+    }
+
+    /// Accepts responses from any ECU
+    pub fn response_filter(&self) -> CanFilter {
+        match self {
+            Addressing::Standard => CanFilter {
+                id: 0x7E8,
+                mask: 0x7F8,
+                extended: false,
+            },
+            Addressing::Extended => CanFilter {
+                id: 0x18DA_F100,
+                mask: 0x1FFF_FF00,
+                extended: true,
+            },
+        }
+    }
+
+    /// Accepts every physically addressed diagnostic ID, which also covers the answers to
+    /// custom PIDs with their own header
+    pub fn diagnostic_filter(&self) -> CanFilter {
+        match self {
+            Addressing::Standard => CanFilter {
+                id: 0x700,
+                mask: 0x700,
+                extended: false,
+            },
+            Addressing::Extended => CanFilter {
+                id: 0x18DA_0000,
+                mask: 0x1FFF_0000,
+                extended: true,
+            },
+        }
+    }
+}
+
+/// ISO 15765-4 CAN protocols, numbered as by the ELM327 (`ATSP6` to `ATSP9`)
+#[repr(u8)]
+#[derive(strum::FromRepr, strum::EnumIter, Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    #[default]
+    Can11Bit500k = 6,
+    Can29Bit500k = 7,
+    Can11Bit250k = 8,
+    Can29Bit250k = 9,
+}
+
+impl Protocol {
+    pub fn addressing(&self) -> Addressing {
+        match self {
+            Protocol::Can11Bit500k | Protocol::Can11Bit250k => Addressing::Standard,
+            Protocol::Can29Bit500k | Protocol::Can29Bit250k => Addressing::Extended,
+        }
+    }
+
+    /// Bits per second
+    pub fn bitrate(&self) -> u32 {
+        match self {
+            Protocol::Can11Bit500k | Protocol::Can29Bit500k => 500_000,
+            Protocol::Can11Bit250k | Protocol::Can29Bit250k => 250_000,
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bits = match self.addressing() {
+            Addressing::Standard => 11,
+            Addressing::Extended => 29,
+        };
+        write!(
+            f,
+            "ISO 15765-4 CAN ({} bit ID, {} kbaud)",
+            bits,
+            self.bitrate() / 1000
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ObdDriverConfig {
+    pub addressing: Addressing,
+    pub isotp: isotp::IsoTpConfig,
+    pub response_timeout: Duration,
+    /// How long to wait after an ECU reports the response is pending (P2*)
+    pub response_pending_timeout: Duration,
+    /// How many times a request is repeated after a bus error, a broken transfer or a busy ECU.
+    /// Unanswered requests aren't repeated.
+    pub retries: u8,
+}
+
+impl Default for ObdDriverConfig {
+    fn default() -> Self {
+        Self {
+            addressing: Default::default(),
+            isotp: Default::default(),
+            response_timeout: Duration::from_millis(100),
+            response_pending_timeout: Duration::from_secs(5),
+            retries: 2,
+        }
+    }
+}
+
+/// Running totals kept by the driver for diagnostics
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DriverStats {
+    pub requests: u32,
+    pub responses: u32,
+    pub timeouts: u32,
+    pub negative_responses: u32,
+    /// Responses that couldn't be reassembled or decoded
+    pub malformed_responses: u32,
+    pub transport_errors: u32,
+    pub retries: u32,
+    pub bus_off_recoveries: u32,
+    /// Frames and messages that didn't answer the request in progress
+    pub unsolicited: u32,
+}
+
+/// An ISO-TP payload along with the ECU that sent it
+type RawResponse = (CanId, Vec<u8>);
+
+/// Each ECU's answer to a functional request, keyed by the ID it responded on
+pub type EcuResponses<E> = BTreeMap<CanId, Result<ObdReadableData, ObdError<E>>>;
+
+/// The ID to send physical requests to the ECU that responds on `ecu` on
+fn physical_request_id<E>(ecu: CanId) -> Result<CanId, ObdError<E>> {
+    isotp::physical_request_id(ecu).ok_or(ObdError::UnknownEcu(ecu))
+}
+
+fn is_response_pending(payload: &[u8]) -> bool {
+    matches!(payload, [0x7F, _, code, ..] if *code == NegativeResponseCode::ResponsePending as u8)
+}
+
+/// Whether `response` answers `request`: a positive response to its service echoing the PID
+/// (and frame number in mode 02) or UDS parameter, or a negative response to its service.
+/// An ECU leaves out the PIDs it doesn't support, so the answer to a mode 01 request for several
+/// PIDs may start with any of them.
+fn answers(request: &[u8], response: &[u8]) -> bool {
+    let echoed = match ObdMode::from_repr(request[0]) {
+        Some(ObdMode::QueryNow) => {
+            return match response {
+                [0x7F, service, ..] => *service == request[0],
+                [0x41, pid, ..] => request[1..].contains(pid),
+                _ => false,
+            };
+        }
+        Some(ObdMode::VehicleInformation) => 1,
+        Some(ObdMode::QueryFreezeFrame) => 2,
+        Some(_) => 0,
+        None => uds::Service::from_repr(request[0]).map_or(0, |service| service.echoed_len()),
+    };
+    match response {
+        [0x7F, service, ..] => *service == request[0],
+        [service, ..] => {
+            *service == request[0].wrapping_add(0x40)
+                && response.get(1..=echoed) == request.get(1..=echoed)
+        }
+        [] => false,
+    }
+}
+
+/// Traffic that isn't an answer to the request in progress, such as a late response to an
+/// earlier request that timed out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unsolicited {
+    /// A frame left in the receive queue before a request was sent
+    Frame(CanFrame),
+    /// A complete message that doesn't answer the current request
+    Response(CanId, Vec<u8>),
+}
+
+type Listener = Box<dyn FnMut(&Unsolicited)>;
+
+pub struct ObdDriver<T: CanTransport> {
+    transport: T,
+    addressing: Addressing,
+    protocol: Option<Protocol>,
+    isotp: isotp::IsoTpConfig,
+    receiver: isotp::Receiver,
+    response_timeout: Duration,
+    response_pending_timeout: Duration,
+    retries: u8,
+    stats: DriverStats,
+    listener: Option<Listener>,
+    supported: BTreeMap<CanId, BTreeSet<u8>>,
+    vin: Option<String>,
+}
+
+/// Proof that the user explicitly agreed to clearing DTCs, obtained from [`ClearChallenge`]
+#[derive(Debug)]
+pub struct ClearConfirmation(());
+
+/// Two step confirmation for clearing DTCs: a challenge is issued and must be echoed back
+/// before it expires
+#[derive(Debug, Default)]
+pub struct ClearChallenge {
+    pending: Option<(u32, Instant)>,
+}
+
+impl ClearChallenge {
+    pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Starts a new confirmation, replacing any outstanding one; `nonce` should be random
+    pub fn issue(&mut self, nonce: u32, now: Instant) -> u32 {
+        self.pending = Some((nonce, now));
+        nonce
+    }
+
+    /// Consumes the outstanding challenge, confirming only if `response` matches in time
+    pub fn confirm(&mut self, response: u32, now: Instant) -> Option<ClearConfirmation> {
+        let (nonce, issued) = self.pending.take()?;
+        (response == nonce && now.saturating_duration_since(issued) <= Self::TIMEOUT)
+            .then_some(ClearConfirmation(()))
+    }
+}
+
+pub struct ObdQuery {
+    pid: Vec<PID>,
+    mode: ObdMode,
+    frame: u8,
+    // Answered with ObdReadableData::Values, even for a single PID
+    multiple: bool,
+}
+
+impl ObdQuery {
+    pub fn new(mode: ObdMode, pid: Option<PID>) -> Self {
+        Self {
+            mode,
+            pid: pid.map(|p| vec![p]).unwrap_or_default(),
+            frame: 0,
+            multiple: false,
+        }
+    }
+
+    /// Reads up to six mode 01 PIDs in one request, answered with [`ObdReadableData::Values`]
+    /// however many are asked for
+    pub fn multiple(pids: &[PID]) -> Option<Self> {
+        if pids.is_empty() || pids.len() > 6 {
+            return None;
+        }
+
+        Some(Self {
+            mode: ObdMode::QueryNow,
+            pid: pids.to_vec(),
+            frame: 0,
+            multiple: true,
+        })
+    }
+
+    /// Reads `pid` as it was when freeze frame `frame` was stored
+    pub fn freeze_frame(pid: PID, frame: u8) -> Self {
+        Self {
+            mode: ObdMode::QueryFreezeFrame,
+            pid: vec![pid],
+            frame,
+            multiple: false,
+        }
+    }
+
+    fn assemble(&self) -> Vec<u8> {
+        (ObdRequest {
+            mode: self.mode,
+            data: &self.pid,
+            frame: self.frame,
+        })
+        .assemble()
+        .unwrap()
+    }
+}
+
+/// The conditions at the moment a DTC was stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreezeFrame {
+    pub dtc: DiagnosticTroubleCode,
+    pub values: Vec<(PID, Quantity)>,
+}
+
+impl<T: CanTransport> ObdDriver<T> {
+    pub fn try_new(mut transport: T, config: &ObdDriverConfig) -> Result<Self, ObdError<T::Error>> {
+        transport
+            .set_filter(config.addressing.response_filter())
+            .map_err(ObdError::Transport)?;
+
+        Ok(Self {
+            transport,
+            addressing: config.addressing,
+            protocol: None,
+            isotp: config.isotp.clone(),
+            receiver: isotp::Receiver::default(),
+            response_timeout: config.response_timeout,
+            response_pending_timeout: config.response_pending_timeout,
+            retries: config.retries,
+            stats: DriverStats::default(),
+            listener: None,
+            supported: BTreeMap::new(),
+            vin: None,
+        })
+    }
+
+    /// Tries each protocol, starting with `preferred`, until an ECU answers. `open` creates a
+    /// transport running at the protocol's bitrate; the transport for the previous attempt is
+    /// dropped first.
+    pub fn detect(
+        mut open: impl FnMut(Protocol) -> Result<T, T::Error>,
+        preferred: Option<Protocol>,
+        config: &ObdDriverConfig,
+    ) -> Result<Self, ObdError<T::Error>> {
+        let candidates = preferred
+            .into_iter()
+            .chain(Protocol::iter().filter(|&protocol| Some(protocol) != preferred));
+
+        for protocol in candidates {
+            let config = ObdDriverConfig {
+                addressing: protocol.addressing(),
+                ..config.clone()
+            };
+            let transport = match open(protocol) {
+                Ok(transport) => transport,
+                Err(e) => {
+                    log::warn!("Couldn't open the bus for {}: {:?}", protocol, e);
+                    continue;
+                }
+            };
+            let mut driver = Self::try_new(transport, &config)?;
+
+            match driver.probe() {
+                Ok(true) => {
+                    log::info!("Detected {}", protocol);
+                    driver.protocol = Some(protocol);
+                    return Ok(driver);
+                }
+                Ok(false) => log::info!("No response on {}", protocol),
+                // A bitrate mismatch shows up as bus errors
+                Err(e) => log::info!("Couldn't probe {}: {:?}", protocol, e),
+            }
+        }
+
+        Err(ObdError::ProtocolNotDetected)
+    }
+
+    /// The protocol found by [`ObdDriver::detect`]
+    pub fn protocol(&self) -> Option<Protocol> {
+        self.protocol
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn stats(&self) -> DriverStats {
+        self.stats
+    }
+
+    pub fn bus_status(&mut self) -> Result<BusStatus, ObdError<T::Error>> {
+        self.transport.status().map_err(ObdError::Transport)
+    }
+
+    /// Calls `listener` with traffic that doesn't answer a request instead of discarding it
+    pub fn set_listener(&mut self, listener: impl FnMut(&Unsolicited) + 'static) {
+        self.listener = Some(Box::new(listener));
+    }
+
+    fn unsolicited(&mut self, traffic: Unsolicited) {
+        log::debug!("Unsolicited {:?}", traffic);
+        self.stats.unsolicited += 1;
+        if let Some(listener) = &mut self.listener {
+            listener(&traffic);
+        }
+    }
+
+    /// Empties the receive queue so a late response can't be taken for the next one
+    fn drain(&mut self) -> Result<(), ObdError<T::Error>> {
+        while let Some(frame) = self
+            .transport
+            .receive(Duration::ZERO)
+            .map_err(|e| self.counted(ObdError::Transport(e)))?
+        {
+            self.unsolicited(Unsolicited::Frame(frame));
+        }
+        Ok(())
+    }
+
+    /// Restarts the controller if it went bus-off, typically after being plugged in while the
+    /// engine was cranking
+    fn recover_bus(&mut self) -> Result<(), ObdError<T::Error>> {
+        let status = self.bus_status()?;
+        if status.state == BusState::BusOff {
+            log::warn!(
+                "Bus off (TEC {}, REC {}), recovering",
+                status.tx_error_counter,
+                status.rx_error_counter
+            );
+            self.transport
+                .recover()
+                .map_err(|e| self.counted(ObdError::Transport(e)))?;
+            self.stats.bus_off_recoveries += 1;
+        }
+        Ok(())
+    }
+
+    /// Counts `err` and tells whether the request that caused it is worth repeating
+    fn record_error(&mut self, err: &ObdError<T::Error>) -> bool {
+        match err {
+            ObdError::Transport(_) => {
+                self.stats.transport_errors += 1;
+                true
+            }
+            ObdError::IsoTp(isotp::IsoTpError::Timeout) => {
+                self.stats.timeouts += 1;
+                false
+            }
+            ObdError::IsoTp(
+                isotp::IsoTpError::InvalidFrame
+                | isotp::IsoTpError::UnexpectedFrame
+                | isotp::IsoTpError::SequenceMismatch { .. },
+            ) => {
+                self.stats.malformed_responses += 1;
+                true
+            }
+            ObdError::IsoTp(_) => false,
+            ObdError::MalformedResponse => {
+                self.stats.malformed_responses += 1;
+                false
+            }
+            ObdError::NegativeResponse { code, .. } => {
+                self.stats.negative_responses += 1;
+                *code == NegativeResponseCode::BusyRepeatRequest as u8
+            }
+            ObdError::ProtocolNotDetected | ObdError::UnknownEcu(_) => false,
+        }
+    }
+
+    /// Counts `err` on its way back to the caller
+    fn counted(&mut self, err: ObdError<T::Error>) -> ObdError<T::Error> {
+        self.record_error(&err);
+        err
+    }
+
+    /// Transmits a request once the receive queue is empty, recovering from bus-off and
+    /// retrying transport errors
+    fn send(&mut self, to: CanId, payload: &[u8]) -> Result<(), ObdError<T::Error>> {
+        self.stats.requests += 1;
+        self.transmit(to, payload)
+    }
+
+    /// Does the work of [`Self::send`] without counting a new request, so repeated requests
+    /// count once
+    fn transmit(&mut self, to: CanId, payload: &[u8]) -> Result<(), ObdError<T::Error>> {
+        self.drain()?;
+        self.receiver.clear();
+        let mut attempt = 0;
+        loop {
+            let err = match isotp::transmit(&mut self.transport, &self.isotp, to, payload) {
+                Ok(()) => return Ok(()),
+                Err(e) => ObdError::from(e),
+            };
+            let retry = self.record_error(&err);
+            if matches!(err, ObdError::Transport(_)) {
+                self.recover_bus()?;
+            }
+            if !retry || attempt == self.retries {
+                return Err(err);
+            }
+            attempt += 1;
+            self.stats.retries += 1;
+            log::info!("Retrying request {:?} after {:?}", payload, err);
+        }
+    }
+
+    /// Sends `payload` and waits for the response to it, from `from` if given, repeating the
+    /// request after errors that may not happen again
+    fn request(
+        &mut self,
+        to: CanId,
+        payload: &[u8],
+        from: Option<CanId>,
+    ) -> Result<RawResponse, ObdError<T::Error>> {
+        self.stats.requests += 1;
+        let mut attempt = 0;
+        loop {
+            self.transmit(to, payload)?;
+            let err = match self.receive_response(payload, from) {
+                Ok(response) => return Ok(response),
+                Err(e) => e,
+            };
+            let retry = self.record_error(&err);
+            if matches!(err, ObdError::Transport(_)) {
+                self.recover_bus()?;
+            }
+            if !retry || attempt == self.retries {
+                return Err(err);
+            }
+            attempt += 1;
+            self.stats.retries += 1;
+            log::info!("Retrying request {:?} after {:?}", payload, err);
+        }
+    }
+
+    /// Whether any ECU answers a request for the first supported PID bitmap
+    fn probe(&mut self) -> Result<bool, ObdError<T::Error>> {
+        let request = ObdQuery::new(ObdMode::QueryNow, Some(PID::FirstCap)).assemble();
+        self.send(self.addressing.functional_request_id(), &request)?;
+
+        Ok(self
+            .receive_all(&request)?
+            .iter()
+            .any(|(_, payload)| payload.starts_with(&[0x41, 0x00])))
+    }
+
+    /// Receives every response to `request` that arrives within the response window, which is
+    /// extended while any ECU reports its response is pending. Responses that fail to reassemble
+    /// are skipped.
+    fn receive_all(&mut self, request: &[u8]) -> Result<Vec<RawResponse>, ObdError<T::Error>> {
+        let mut responses = vec![];
+        let mut deadline = Instant::now() + self.response_timeout;
+        loop {
+            match self
+                .receiver
+                .receive(&mut self.transport, &self.isotp, deadline)
+            {
+                Ok((ecu, payload)) if !answers(request, &payload) => {
+                    self.unsolicited(Unsolicited::Response(ecu, payload));
+                }
+                Ok((ecu, payload)) if is_response_pending(&payload) => {
+                    log::info!("{:#X} is still processing the request", ecu.raw());
+                    deadline = Instant::now() + self.response_pending_timeout;
+                }
+                Ok(response) => {
+                    self.stats.responses += 1;
+                    responses.push(response);
+                }
+                Err(isotp::IsoTpError::Timeout) => {
+                    if responses.is_empty() {
+                        self.record_error(&ObdError::IsoTp(isotp::IsoTpError::Timeout));
+                    }
+                    return Ok(responses);
+                }
+                Err(isotp::IsoTpError::Transport(e)) => {
+                    return Err(self.counted(ObdError::Transport(e)));
+                }
+                // One ECU's broken transfer shouldn't lose what the others sent
+                Err(e) => {
+                    log::warn!("Dropped a broken response: {:?}", e);
+                    self.record_error(&e.into());
+                }
+            }
+        }
+    }
+
+    /// Receives the response to `request`, from `from` if given, waiting longer while the ECU
+    /// reports it is still processing the request. Other negative responses become
+    /// [`ObdError::NegativeResponse`].
+    fn receive_response(
+        &mut self,
+        request: &[u8],
+        from: Option<CanId>,
+    ) -> Result<RawResponse, ObdError<T::Error>> {
+        let service = request[0];
+        let mut deadline = Instant::now() + self.response_timeout;
+        loop {
+            let (ecu, payload) =
+                self.receiver
+                    .receive(&mut self.transport, &self.isotp, deadline)?;
+            match payload[..] {
+                _ if from.is_some_and(|from| from != ecu) || !answers(request, &payload) => {
+                    self.unsolicited(Unsolicited::Response(ecu, payload));
+                }
+                _ if is_response_pending(&payload) => {
+                    log::info!("{:#X} is still processing {:#04X}", ecu.raw(), service);
+                    deadline = Instant::now() + self.response_pending_timeout;
+                }
+                [0x7F, rejected, code, ..] if rejected == service => {
+                    return Err(ObdError::NegativeResponse { service, code })
+                }
+                _ => {
+                    self.stats.responses += 1;
+                    return Ok((ecu, payload));
+                }
+            }
+        }
+    }
+
+    /// Walks the supported PID bitmaps of every responding ECU, stopping once no ECU advertises
+    /// the next range
+    pub fn discover_supported_pids(&mut self) -> Result<(), ObdError<T::Error>> {
+        self.supported.clear();
+
+        for range in [
+            PID::FirstCap,
+            PID::SecondCap,
+            PID::ThirdCap,
+            PID::FourthCap,
+            PID::FifthCap,
+            PID::SixthCap,
+            PID::SeventhCap,
+            PID::EighthCap,
+        ] {
+            let request = (ObdRequest {
+                mode: ObdMode::QueryNow,
+                data: &[range],
+                frame: 0,
+            })
+            .assemble()
+            .unwrap();
+
+            self.send(self.addressing.functional_request_id(), &request)?;
+
+            let mut next_range = false;
+            for (ecu, payload) in self.receive_all(&request)? {
+                if payload.len() < 2 || payload[..2] != [0x41, range as u8] {
+                    log::warn!(
+                        "Ignoring unexpected response from {:#X}: {:?}",
+                        ecu.raw(),
+                        payload
+                    );
+                    continue;
+                }
+
+                let Ok(ObdReadableData::SupportedPids(pids)) =
+                    ObdReadableData::try_from(ObdResponse {
+                        mode: ObdMode::QueryNow,
+                        format: Some(&range),
+                        data: &payload[2..],
+                    })
+                else {
+                    return Err(self.counted(ObdError::MalformedResponse));
+                };
+
+                next_range |= pids.last() == Some(&(range as u8).wrapping_add(0x20));
+                self.supported.entry(ecu).or_default().extend(pids);
+            }
+
+            if !next_range {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Known PIDs supported by at least one ECU as of the last discovery
+    pub fn supported_pids(&self) -> HashSet<PID> {
+        self.supported
+            .values()
+            .flatten()
+            .filter_map(|&pid| PID::from_repr(pid))
+            .collect()
+    }
+
+    /// Raw supported PID numbers reported by each ECU
+    pub fn supported_pids_by_ecu(&self) -> &BTreeMap<CanId, BTreeSet<u8>> {
+        &self.supported
+    }
+
+    /// Whether any ECU supports `pid`; every PID is assumed supported until discovery has run
+    pub fn is_supported(&self, pid: PID) -> bool {
+        self.supported.is_empty()
+            || self
+                .supported
+                .values()
+                .any(|pids| pids.contains(&(pid as u8)))
+    }
+
+    /// Reads the DTC that stored freeze frame `frame` along with the values of `pids` at that
+    /// moment, or `None` if no freeze frame is stored
+    pub fn read_freeze_frame(
+        &mut self,
+        frame: u8,
+        pids: &[PID],
+    ) -> Result<Option<FreezeFrame>, ObdError<T::Error>> {
+        let dtc = match self.query(&ObdQuery::freeze_frame(PID::FreezeFrameDtc, frame))? {
+            ObdReadableData::DTC(codes) => match codes.first() {
+                Some(&dtc) => dtc,
+                None => return Ok(None),
+            },
+            _ => return Err(self.counted(ObdError::MalformedResponse)),
+        };
+
+        let mut values = vec![];
+        for &pid in pids {
+            match self.query(&ObdQuery::freeze_frame(pid, frame)) {
+                Ok(ObdReadableData::Value(value)) => values.push((pid, value)),
+                // PIDs that weren't captured in the frame go unanswered or are out of range
+                Err(ObdError::IsoTp(isotp::IsoTpError::Timeout)) => {}
+                Err(ObdError::NegativeResponse { code, .. })
+                    if code == NegativeResponseCode::RequestOutOfRange as u8 => {}
+                Ok(_) => return Err(self.counted(ObdError::MalformedResponse)),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(Some(FreezeFrame { dtc, values }))
+    }
+
+    /// Requests a Mode 09 InfoType from the first ECU to respond
+    pub fn read_vehicle_info(&mut self, info: InfoType) -> Result<VehicleInfo, ObdError<T::Error>> {
+        let (_, payload) = self.request(
+            self.addressing.functional_request_id(),
+            &[ObdMode::VehicleInformation as u8, info as u8],
+            None,
+        )?;
+        if payload.len() < 2 || payload[..2] != [0x49, info as u8] {
+            log::error!("Picked up the wrong packet: {:?}", payload);
+            return Err(self.counted(ObdError::MalformedResponse));
+        }
+
+        VehicleInfo::decode(info, &payload[2..])
+            .map_err(|()| self.counted(ObdError::MalformedResponse))
+    }
+
+    /// Reads the VIN, keeping it for [`ObdDriver::vin`]
+    pub fn read_vin(&mut self) -> Result<&str, ObdError<T::Error>> {
+        let VehicleInfo::Vin(vin) = self.read_vehicle_info(InfoType::Vin)? else {
+            unreachable!("decoded as the requested InfoType");
+        };
+        Ok(self.vin.insert(vin))
+    }
+
+    /// The VIN as of the last [`ObdDriver::read_vin`]
+    pub fn vin(&self) -> Option<&str> {
+        self.vin.as_deref()
+    }
+
+    /// Clears stored DTCs and freeze frames on every ECU found by discovery, returning the ECUs
+    /// that confirmed. This also resets the readiness monitors, hence the confirmation.
+    ///
+    /// ECUs are cleared one at a time in ascending ID order and the first failure is returned,
+    /// so the ECUs before the failing one have already been cleared by then.
+    pub fn clear_dtcs(
+        &mut self,
+        _confirmation: ClearConfirmation,
+    ) -> Result<Vec<CanId>, ObdError<T::Error>> {
+        if self.supported.is_empty() {
+            self.discover_supported_pids()?;
+        }
+
+        let ecus: Vec<CanId> = self.supported.keys().copied().collect();
+        if ecus.is_empty() {
+            log::error!("No ECU to clear");
+            return Err(self.counted(ObdError::IsoTp(isotp::IsoTpError::Timeout)));
+        }
+        for &ecu in &ecus {
+            let (responder, payload) = self.request(
+                physical_request_id(ecu)?,
+                &[ObdMode::ClearDTC as u8],
+                Some(ecu),
+            )?;
+            match payload[..] {
+                [0x44, ..] => {}
+                _ => {
+                    log::error!(
+                        "Unexpected reply to clear from {:#X}: {:?}",
+                        responder.raw(),
+                        payload
+                    );
+                    return Err(self.counted(ObdError::MalformedResponse));
+                }
+            }
+        }
+
+        Ok(ecus)
+    }
+
+    /// Sends `query` to every ECU and returns the first answer; see [`ObdDriver::query_all`] when
+    /// more than one ECU may respond. The answers to a query for several PIDs are merged, since
+    /// each ECU only reports the PIDs it supports.
+    pub fn query(&mut self, query: &ObdQuery) -> Result<ObdReadableData, ObdError<T::Error>> {
+        if query.multiple {
+            return self.query_merged(query);
+        }
+
+        let (_, payload) = self.request(
+            self.addressing.functional_request_id(),
+            &query.assemble(),
+            None,
+        )?;
+        self.decode_response(query, &payload)
+    }
+
+    /// Merges the values every ECU reported for a query for several PIDs, keeping the one from
+    /// the lowest ID when more than one ECU reports a PID
+    fn query_merged(&mut self, query: &ObdQuery) -> Result<ObdReadableData, ObdError<T::Error>> {
+        let responses = self.query_all(query)?;
+        if responses.is_empty() {
+            return Err(ObdError::IsoTp(isotp::IsoTpError::Timeout));
+        }
+
+        let mut merged = HashMap::new();
+        let mut error = None;
+        for response in responses.into_values() {
+            match response {
+                Ok(ObdReadableData::Values(values)) => {
+                    for (pid, value) in values {
+                        merged.entry(pid).or_insert(value);
+                    }
+                }
+                Ok(_) => unreachable!("decoded as values"),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+
+        match error {
+            Some(e) if merged.is_empty() => Err(e),
+            _ => Ok(ObdReadableData::Values(merged)),
+        }
+    }
+
+    /// Sends `query` to the ECU that responds on `ecu` only
+    pub fn query_ecu(
+        &mut self,
+        ecu: CanId,
+        query: &ObdQuery,
+    ) -> Result<ObdReadableData, ObdError<T::Error>> {
+        let (_, payload) = self.request(physical_request_id(ecu)?, &query.assemble(), Some(ecu))?;
+        self.decode_response(query, &payload)
+    }
+
+    /// Reads a user defined PID from the ECU in its header, or the first ECU to answer
+    pub fn query_custom(&mut self, pid: &CustomPid) -> Result<Quantity, ObdError<T::Error>> {
+        let (_, payload) = match pid.header {
+            Some(header) => {
+                let ecu = pid.response_id().ok_or(ObdError::UnknownEcu(header))?;
+                // The answer may come from outside the usual response IDs, or even use the
+                // other ID length, so only it is let through until it arrives
+                self.transport
+                    .set_filter(CanFilter::exact(ecu))
+                    .map_err(|e| self.counted(ObdError::Transport(e)))?;
+                let response = self.request(header, &pid.request, Some(ecu));
+                self.transport
+                    .set_filter(self.addressing.response_filter())
+                    .map_err(|e| self.counted(ObdError::Transport(e)))?;
+                response?
+            }
+            None => self.request(self.addressing.functional_request_id(), &pid.request, None)?,
+        };
+
+        let value = payload
+            .get(pid.request.len()..)
+            .and_then(|data| pid.decode(data));
+        value.ok_or_else(|| {
+            log::error!("Couldn't decode {} from {:?}", pid.name, payload);
+            self.counted(ObdError::MalformedResponse)
+        })
+    }
+
+    /// Sends a raw request to the ECU that responds on `ecu` and returns its positive response
+    pub(crate) fn request_ecu(
+        &mut self,
+        ecu: CanId,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, ObdError<T::Error>> {
+        let (_, response) = self.request(physical_request_id(ecu)?, payload, Some(ecu))?;
+        Ok(response)
+    }
+
+    /// Sends a raw request to the ECU that responds on `ecu` without waiting for an answer
+    pub(crate) fn send_ecu(
+        &mut self,
+        ecu: CanId,
+        payload: &[u8],
+    ) -> Result<(), ObdError<T::Error>> {
+        self.send(physical_request_id(ecu)?, payload)
+    }
+
+    /// Sends `query` to every ECU and collects each answer within the response window by the
+    /// ID it was sent from
+    pub fn query_all(
+        &mut self,
+        query: &ObdQuery,
+    ) -> Result<EcuResponses<T::Error>, ObdError<T::Error>> {
+        let request = query.assemble();
+        self.send(self.addressing.functional_request_id(), &request)?;
+
+        Ok(self
+            .receive_all(&request)?
+            .into_iter()
+            .map(|(ecu, payload)| {
+                let response = match payload[..] {
+                    [0x7F, service, code, ..] => {
+                        Err(self.counted(ObdError::NegativeResponse { service, code }))
+                    }
+                    _ => self.decode_response(query, &payload),
+                };
+                (ecu, response)
+            })
+            .collect())
+    }
+
+    /// Decodes `payload` as the answer to `query`, counting it if malformed
+    fn decode_response(
+        &mut self,
+        query: &ObdQuery,
+        payload: &[u8],
+    ) -> Result<ObdReadableData, ObdError<T::Error>> {
+        Self::decode_payload(query, payload).map_err(|e| self.counted(e))
+    }
+
+    fn decode_payload(
+        query: &ObdQuery,
+        payload: &[u8],
+    ) -> Result<ObdReadableData, ObdError<T::Error>> {
+        if query.multiple {
+            if payload.first() != Some(&0x41) {
+                log::error!("Picked up the wrong packet: {:?}", payload);
+                return Err(ObdError::MalformedResponse);
+            }
+            return decode_pids(&query.pid, &payload[1..])
+                .map(ObdReadableData::Values)
+                .map_err(|()| ObdError::MalformedResponse);
+        }
+
+        if payload.first() != Some(&(0x40 + query.mode as u8))
+            || (!query.pid.is_empty() && payload.get(1) != Some(&(query.pid[0] as u8)))
+            || (query.mode == ObdMode::QueryFreezeFrame && payload.get(2) != Some(&query.frame))
+        {
+            log::error!("Picked up the wrong packet: {:?}", payload);
+            return Err(ObdError::MalformedResponse);
+        }
+
+        let header_len = match query.mode {
+            ObdMode::QueryFreezeFrame => 1 + 2 * query.pid.len(),
+            _ => 1 + query.pid.len(),
+        };
+
+        if payload.len() <= header_len {
+            log::error!("Recieved empty packet: {:?}", payload);
+            return Err(ObdError::MalformedResponse);
+        }
+
+        let response = ObdResponse {
+            data: &payload[header_len..],
+            mode: query.mode,
+            format: query.pid.first(),
+        };
+
+        match ObdReadableData::try_from(response) {
+            Ok(res) => Ok(res),
+            Err(()) => Err(ObdError::MalformedResponse),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    fn decode(pid: PID, data: &[u8]) -> Result<ObdReadableData, ()> {
+        ObdReadableData::try_from(ObdResponse {
+            mode: ObdMode::QueryNow,
+            format: Some(&pid),
+            data,
+        })
+    }
+
+    #[test]
+    fn decodes_query_now_formulas() {
+        let cases = [
+            (PID::EngineSpeed, &[0x1A, 0xF8][..], 1726.0, Unit::Rpm),
+            (PID::MassAirFlow, &[0x01, 0x2C], 3.0, Unit::GramsPerSecond),
+            (PID::VehicleSpeed, &[0x32], 50.0, Unit::KilometersPerHour),
+            (PID::ThrottlePosition, &[0xFF], 100.0, Unit::Percent),
+            (PID::ShortTermFuelTrimBankOne, &[0x80], 0.0, Unit::Percent),
+            (
+                PID::Odometer,
+                &[0x00, 0x01, 0x86, 0xA0],
+                10000.0,
+                Unit::Kilometers,
+            ),
+            (PID::EngineCoolantTemperature, &[0x7B], 83.0, Unit::Celsius),
+            (PID::RunTime, &[0x01, 0x00], 256.0, Unit::Seconds),
+        ];
+
+        for (pid, data, value, unit) in cases {
+            assert!(
+                matches!(
+                    decode(pid, data),
+                    Ok(ObdReadableData::Value(q)) if q == Quantity::new(value, unit)
+                ),
+                "{:?}",
+                pid
+            );
+        }
+    }
+
+    #[test]
+    fn converts_decoded_values() {
+        let speed = decode(PID::VehicleSpeed, &[0x64]).unwrap();
+        assert_eq!(speed.value_in(Unit::KilometersPerHour), Some(100.0));
+        assert!((speed.value_in(Unit::MilesPerHour).unwrap() - 62.137).abs() < 1e-3);
+        assert_eq!(speed.value_in(Unit::GramsPerSecond), None);
+
+        let codes = ObdReadableData::DTC(vec![]);
+        assert_eq!(codes.value_in(Unit::Count), None);
+    }
+
+    /// Each PID's value worked out by hand from J1979, independently of its formula
+    fn reference(pid: PID, data: &[u8]) -> f32 {
+        let [a, b, c, d] = [data[0], data[1], data[2], data[3]].map(|byte| byte as f32);
+        match pid {
+            PID::MassAirFlow => (256.0 * a + b) / 100.0,
+            PID::EngineFuelRate => (256.0 * a + b) / 20.0,
+            PID::EngineSpeed => (256.0 * a + b) / 4.0,
+            PID::RunTime
+            | PID::DistanceWithMilOn
+            | PID::DistanceSinceCodesCleared
+            | PID::TimeRunWithMilOn
+            | PID::TimeSinceCodesCleared
+            | PID::EngineReferenceTorque => 256.0 * a + b,
+            PID::VehicleSpeed
+            | PID::IntakeManifoldAbsolutePressure
+            | PID::WarmUpsSinceCodesCleared
+            | PID::AbsoluteBarometricPressure => a,
+            PID::ThrottlePosition
+            | PID::FuelTankLevelInput
+            | PID::RelativeThrottlePosition
+            | PID::CalculatedEngineLoad
+            | PID::CommandedEgr
+            | PID::CommandedEvaporativePurge
+            | PID::AbsoluteThrottlePositionB
+            | PID::AbsoluteThrottlePositionC
+            | PID::AcceleratorPedalPositionD
+            | PID::AcceleratorPedalPositionE
+            | PID::AcceleratorPedalPositionF
+            | PID::CommandedThrottleActuator
+            | PID::EthanolFuelPercentage
+            | PID::RelativeAcceleratorPedalPosition
+            | PID::HybridBatteryRemainingLife => a / 2.55,
+            PID::Odometer => {
+                (a * 2.0_f32.powi(24) + b * 2.0_f32.powi(16) + c * 2.0_f32.powi(8) + d) / 10.0
+            }
+            PID::ShortTermFuelTrimBankOne
+            | PID::LongTermFuelTrimBankOne
+            | PID::ShortTermFuelTrimBankTwo
+            | PID::LongTermFuelTrimBankTwo
+            | PID::EgrError => (a / 1.28) - 100.0,
+            PID::EngineCoolantTemperature
+            | PID::IntakeAirTemperature
+            | PID::AmbientAirTemperature
+            | PID::EngineOilTemperature => a - 40.0,
+            PID::FuelPressure => 3.0 * a,
+            PID::TimingAdvance => a / 2.0 - 64.0,
+            PID::OxygenSensorOneVoltage
+            | PID::OxygenSensorTwoVoltage
+            | PID::OxygenSensorThreeVoltage
+            | PID::OxygenSensorFourVoltage
+            | PID::OxygenSensorFiveVoltage
+            | PID::OxygenSensorSixVoltage
+            | PID::OxygenSensorSevenVoltage
+            | PID::OxygenSensorEightVoltage => a / 200.0,
+            PID::FuelRailPressure => 0.079 * (256.0 * a + b),
+            PID::FuelRailGaugePressure | PID::FuelRailAbsolutePressure => 10.0 * (256.0 * a + b),
+            PID::WideRangeOxygenSensorOne
+            | PID::WideRangeOxygenSensorTwo
+            | PID::WideRangeOxygenSensorThree
+            | PID::WideRangeOxygenSensorFour
+            | PID::WideRangeOxygenSensorFive
+            | PID::WideRangeOxygenSensorSix
+            | PID::WideRangeOxygenSensorSeven
+            | PID::WideRangeOxygenSensorEight
+            | PID::CommandedAirFuelEquivalenceRatio => (256.0 * a + b) * 2.0 / 65536.0,
+            PID::EvapSystemVaporPressure => i16::from_be_bytes([data[0], data[1]]) as f32 / 4.0,
+            PID::CatalystTemperatureBankOneSensorOne
+            | PID::CatalystTemperatureBankTwoSensorOne
+            | PID::CatalystTemperatureBankOneSensorTwo
+            | PID::CatalystTemperatureBankTwoSensorTwo => (256.0 * a + b) / 10.0 - 40.0,
+            PID::ControlModuleVoltage => (256.0 * a + b) / 1000.0,
+            PID::AbsoluteLoadValue => (256.0 * a + b) / 2.55,
+            PID::MaximumMassAirFlow => 10.0 * a,
+            PID::AbsoluteEvapSystemVaporPressure => (256.0 * a + b) / 200.0,
+            PID::WideEvapSystemVaporPressure => 256.0 * a + b - 32767.0,
+            PID::FuelInjectionTiming => (256.0 * a + b) / 128.0 - 210.0,
+            PID::DriverDemandEngineTorque | PID::ActualEngineTorque => a - 125.0,
+            pid => panic!("no reference for {:?}", pid),
+        }
+    }
+
+    #[test]
+    fn formulas_match_reference() {
+        for pid in PID::iter() {
+            assert_eq!(pid.has_value(), pid.formula().is_some(), "{:?}", pid);
+        }
+
+        // Every value of the first two bytes the formula reads, and a few of the others
+        for pid in PID::iter().filter(PID::has_value) {
+            let bytes_needed = formulas()[&pid].bytes_needed();
+            let others: &[[u8; 2]] = match bytes_needed {
+                0..=2 => &[[0x00, 0x00]],
+                _ => &[[0x00, 0x00], [0x12, 0x34], [0xFF, 0xFF]],
+            };
+            for &[c, d] in others {
+                for ab in 0..1u32 << (8 * bytes_needed.min(2)) {
+                    let [a, b] = match bytes_needed {
+                        1 => [ab as u8, 0],
+                        _ => (ab as u16).to_be_bytes(),
+                    };
+                    let data = [a, b, c, d];
+                    let data = &data[..pid.data_len()];
+                    let Ok(ObdReadableData::Value(value)) = decode(pid, data) else {
+                        panic!("{:?} didn't decode {:?}", pid, data);
+                    };
+                    assert_eq!(
+                        value.value,
+                        reference(pid, &[a, b, c, d]),
+                        "{:?} {:?}",
+                        pid,
+                        data
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_every_pid() {
+        #[rustfmt::skip]
+        let table: &[(PID, &[u8], f32)] = &[
+            (PID::CalculatedEngineLoad, &[0xFF], 100.0),
+            (PID::EngineCoolantTemperature, &[0x7B], 83.0),
+            (PID::ShortTermFuelTrimBankOne, &[0x80], 0.0),
+            (PID::LongTermFuelTrimBankOne, &[0x9A], 154.0 / 1.28 - 100.0),
+            (PID::ShortTermFuelTrimBankTwo, &[0x00], -100.0),
+            (PID::LongTermFuelTrimBankTwo, &[0xFF], 255.0 / 1.28 - 100.0),
+            (PID::FuelPressure, &[0x64], 300.0),
+            (PID::IntakeManifoldAbsolutePressure, &[0x65], 101.0),
+            (PID::EngineSpeed, &[0x1A, 0xF8], 1726.0),
+            (PID::VehicleSpeed, &[0x32], 50.0),
+            (PID::TimingAdvance, &[0x94], 10.0),
+            (PID::IntakeAirTemperature, &[0x3C], 20.0),
+            (PID::MassAirFlow, &[0x01, 0x2C], 3.0),
+            (PID::ThrottlePosition, &[0x33], 20.0),
+            (PID::OxygenSensorOneVoltage, &[0x5A, 0xFF], 0.45),
+            (PID::OxygenSensorTwoVoltage, &[0xC8, 0x80], 1.0),
+            (PID::OxygenSensorThreeVoltage, &[0x00, 0x80], 0.0),
+            (PID::OxygenSensorFourVoltage, &[0xFF, 0x80], 1.275),
+            (PID::OxygenSensorFiveVoltage, &[0x14, 0xFF], 0.1),
+            (PID::OxygenSensorSixVoltage, &[0x28, 0xFF], 0.2),
+            (PID::OxygenSensorSevenVoltage, &[0x3C, 0xFF], 0.3),
+            (PID::OxygenSensorEightVoltage, &[0x50, 0xFF], 0.4),
+            (PID::RunTime, &[0x01, 0x00], 256.0),
+            (PID::DistanceWithMilOn, &[0x00, 0x2A], 42.0),
+            (PID::FuelRailPressure, &[0x03, 0xE8], 79.0),
+            (PID::FuelRailGaugePressure, &[0x01, 0xF4], 5000.0),
+            (PID::WideRangeOxygenSensorOne, &[0x80, 0x00, 0x80, 0x00], 1.0),
+            (PID::WideRangeOxygenSensorTwo, &[0x40, 0x00, 0x00, 0x00], 0.5),
+            (PID::WideRangeOxygenSensorThree, &[0xFF, 0xFF, 0x00, 0x00], 2.0 * 65535.0 / 65536.0),
+            (PID::WideRangeOxygenSensorFour, &[0x00, 0x00, 0x00, 0x00], 0.0),
+            (PID::WideRangeOxygenSensorFive, &[0x60, 0x00, 0x00, 0x00], 0.75),
+            (PID::WideRangeOxygenSensorSix, &[0x70, 0x00, 0x00, 0x00], 0.875),
+            (PID::WideRangeOxygenSensorSeven, &[0x90, 0x00, 0x00, 0x00], 1.125),
+            (PID::WideRangeOxygenSensorEight, &[0xA0, 0x00, 0x00, 0x00], 1.25),
+            (PID::CommandedEgr, &[0x80], 128.0 / 2.55),
+            (PID::EgrError, &[0x40], -50.0),
+            (PID::CommandedEvaporativePurge, &[0x00], 0.0),
+            (PID::FuelTankLevelInput, &[0xCC], 80.0),
+            (PID::WarmUpsSinceCodesCleared, &[0x0A], 10.0),
+            (PID::DistanceSinceCodesCleared, &[0x12, 0x34], 4660.0),
+            (PID::EvapSystemVaporPressure, &[0xFF, 0x38], -50.0),
+            (PID::AbsoluteBarometricPressure, &[0x64], 100.0),
+            (PID::CatalystTemperatureBankOneSensorOne, &[0x1F, 0x40], 760.0),
+            (PID::CatalystTemperatureBankTwoSensorOne, &[0x01, 0x90], 0.0),
+            (PID::CatalystTemperatureBankOneSensorTwo, &[0x00, 0x00], -40.0),
+            (PID::CatalystTemperatureBankTwoSensorTwo, &[0x03, 0xE8], 60.0),
+            (PID::ControlModuleVoltage, &[0x36, 0xB0], 14.0),
+            (PID::AbsoluteLoadValue, &[0x00, 0xFF], 100.0),
+            (PID::CommandedAirFuelEquivalenceRatio, &[0x80, 0x00], 1.0),
+            (PID::RelativeThrottlePosition, &[0x1A], 26.0 / 2.55),
+            (PID::AmbientAirTemperature, &[0x28], 0.0),
+            (PID::AbsoluteThrottlePositionB, &[0x66], 40.0),
+            (PID::AbsoluteThrottlePositionC, &[0x99], 60.0),
+            (PID::AcceleratorPedalPositionD, &[0xFF], 100.0),
+            (PID::AcceleratorPedalPositionE, &[0x00], 0.0),
+            (PID::AcceleratorPedalPositionF, &[0x33], 20.0),
+            (PID::CommandedThrottleActuator, &[0x19], 25.0 / 2.55),
+            (PID::TimeRunWithMilOn, &[0x00, 0x3C], 60.0),
+            (PID::TimeSinceCodesCleared, &[0x05, 0xA0], 1440.0),
+            (PID::MaximumMassAirFlow, &[0x19, 0x00, 0x00, 0x00], 250.0),
+            (PID::EthanolFuelPercentage, &[0x26], 38.0 / 2.55),
+            (PID::AbsoluteEvapSystemVaporPressure, &[0x4E, 0x20], 100.0),
+            (PID::WideEvapSystemVaporPressure, &[0x80, 0x00], 1.0),
+            (PID::FuelRailAbsolutePressure, &[0x00, 0x64], 1000.0),
+            (PID::RelativeAcceleratorPedalPosition, &[0x80], 128.0 / 2.55),
+            (PID::HybridBatteryRemainingLife, &[0xE6], 230.0 / 2.55),
+            (PID::EngineOilTemperature, &[0x82], 90.0),
+            (PID::FuelInjectionTiming, &[0x69, 0x00], 0.0),
+            (PID::EngineFuelRate, &[0x00, 0x64], 5.0),
+            (PID::DriverDemandEngineTorque, &[0x7D], 0.0),
+            (PID::ActualEngineTorque, &[0xAF], 50.0),
+            (PID::EngineReferenceTorque, &[0x01, 0x90], 400.0),
+            (PID::Odometer, &[0x00, 0x01, 0x86, 0xA0], 10000.0),
+        ];
+
+        for &(pid, data, expected) in table {
+            let value = match decode(pid, data) {
+                Ok(ObdReadableData::Value(q)) if q.unit == pid.unit() => q.value,
+                other => panic!("{:?} decoded to {:?}", pid, other),
+            };
+            assert!(
+                (value - expected).abs() <= 1e-4 * expected.abs().max(1.0),
+                "{:?}: {} != {}",
+                pid,
+                value,
+                expected
+            );
+
+            // Every byte of the formula is required
+            assert_eq!(data.len(), pid.data_len(), "{:?}", pid);
+            assert!(decode(pid, &data[..data.len() - 1]).is_err(), "{:?}", pid);
+        }
+
+        for pid in PID::iter().filter(PID::has_value) {
+            assert!(
+                table.iter().any(|&(p, _, _)| p == pid),
+                "{:?} has no test case",
+                pid
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_supported_pid_bitmaps() {
+        assert!(matches!(
+            decode(PID::FirstCap, &[0xBE, 0x1F, 0xA8, 0x13]),
+            Ok(ObdReadableData::SupportedPids(pids)) if pids == [
+                0x01, 0x03, 0x04, 0x05, 0x06, 0x07, 0x0C, 0x0D, 0x0E, 0x0F, 0x10, 0x11, 0x13,
+                0x15, 0x1C, 0x1F, 0x20
+            ]
+        ));
+        assert!(matches!(
+            decode(PID::EighthCap, &[0x80, 0x00, 0x00, 0x01]),
+            Ok(ObdReadableData::SupportedPids(pids)) if pids == [0xE1]
+        ));
+        assert!(decode(PID::SecondCap, &[0x80, 0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn decodes_dtc_lists() {
+        let codes = |data: &[u8]| {
+            ObdReadableData::try_from(ObdResponse {
+                mode: ObdMode::QueryDTC,
+                format: None,
+                data,
+            })
+        };
+
+        assert!(matches!(codes(&[0x00]), Ok(ObdReadableData::DTC(d)) if d.is_empty()));
+        assert!(matches!(
+            codes(&[0x02, 0x03, 0x01, 0xC1, 0x00, 0x00, 0x00]),
+            Ok(ObdReadableData::DTC(d)) if d == [
+                DiagnosticTroubleCode::from_bytes([0x03, 0x01]),
+                DiagnosticTroubleCode::from_bytes([0xC1, 0x00]),
+            ]
+        ));
+        // Padded slots are not codes
+        assert!(matches!(
+            codes(&[0x02, 0x00, 0x00, 0x04, 0x20]),
+            Ok(ObdReadableData::DTC(d)) if d == [DiagnosticTroubleCode::from_bytes([0x04, 0x20])]
+        ));
+        assert!(codes(&[0x02, 0x03, 0x01]).is_err());
+        assert!(codes(&[]).is_err());
+
+        let pending = ObdReadableData::try_from(ObdResponse {
+            mode: ObdMode::QueryPendingDTC,
+            format: None,
+            data: &[0x01, 0x01, 0x71],
+        });
+        assert!(matches!(
+            pending,
+            Ok(ObdReadableData::PendingDTC(d)) if d == [DiagnosticTroubleCode::from_bytes([0x01, 0x71])]
+        ));
+        let permanent = ObdReadableData::try_from(ObdResponse {
+            mode: ObdMode::QueryPermanentDTC,
+            format: None,
+            data: &[0x00],
+        });
+        assert!(matches!(permanent, Ok(ObdReadableData::PermanentDTC(d)) if d.is_empty()));
+    }
+
+    #[test]
+    fn decodes_freeze_frames() {
+        let frame = |pid, data| {
+            ObdReadableData::try_from(ObdResponse {
+                mode: ObdMode::QueryFreezeFrame,
+                format: Some(&pid),
+                data,
+            })
+        };
+
+        assert!(matches!(
+            frame(PID::FreezeFrameDtc, &[0x03, 0x01]),
+            Ok(ObdReadableData::DTC(d)) if d == [DiagnosticTroubleCode::from_bytes([0x03, 0x01])]
+        ));
+        assert!(matches!(
+            frame(PID::FreezeFrameDtc, &[0x00, 0x00]),
+            Ok(ObdReadableData::DTC(d)) if d.is_empty()
+        ));
+        assert!(frame(PID::FreezeFrameDtc, &[0x03]).is_err());
+        assert!(matches!(
+            frame(PID::EngineSpeed, &[0x1A, 0xF8]),
+            Ok(ObdReadableData::Value(q)) if q == Quantity::new(1726.0, Unit::Rpm)
+        ));
+    }
+
+    #[test]
+    fn decodes_interleaved_pids() {
+        let requested = [
+            PID::MassAirFlow,
+            PID::ShortTermFuelTrimBankOne,
+            PID::LongTermFuelTrimBankOne,
+            PID::VehicleSpeed,
+        ];
+        // LTFT is left out, as an ECU does for unsupported PIDs
+        let values = decode_pids(&requested, &[0x10, 0x01, 0x2C, 0x06, 0x80, 0x0D, 0x32]).unwrap();
+
+        assert_eq!(values.len(), 3);
+        assert!(matches!(
+            values[&PID::MassAirFlow],
+            ObdReadableData::Value(q) if q == Quantity::new(3.0, Unit::GramsPerSecond)
+        ));
+        assert!(matches!(
+            values[&PID::ShortTermFuelTrimBankOne],
+            ObdReadableData::Value(q) if q == Quantity::new(0.0, Unit::Percent)
+        ));
+        assert!(matches!(
+            values[&PID::VehicleSpeed],
+            ObdReadableData::Value(q) if q == Quantity::new(50.0, Unit::KilometersPerHour)
+        ));
+
+        // Truncated, or a PID that wasn't asked for
+        assert!(decode_pids(&requested, &[0x10, 0x01, 0x2C, 0x0D]).is_err());
+        assert!(decode_pids(&requested, &[0x0C, 0x1A, 0xF8]).is_err());
+
+        assert!(ObdQuery::multiple(&requested).is_some());
+        assert!(ObdQuery::multiple(&[]).is_none());
+        assert!(ObdQuery::multiple(&[PID::EngineSpeed; 7]).is_none());
+    }
+
+    #[test]
+    fn rejects_short_data() {
+        assert!(decode(PID::EngineSpeed, &[0x1A]).is_err());
+        assert!(decode(PID::VehicleSpeed, &[]).is_err());
+        assert!(decode(PID::Odometer, &[0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn assembles_requests() {
+        let mut request = ObdRequest {
+            mode: ObdMode::QueryNow,
+            data: &[PID::EngineSpeed, PID::VehicleSpeed],
+            frame: 0,
+        };
+        assert_eq!(request.assemble(), Ok(vec![0x01, 0x0C, 0x0D]));
+
+        let mut request = ObdRequest {
+            mode: ObdMode::QueryDTC,
+            data: &[PID::EngineSpeed],
+            frame: 0,
+        };
+        assert_eq!(request.assemble(), Ok(vec![0x03]));
+
+        let mut request = ObdRequest {
+            mode: ObdMode::ClearDTC,
+            data: &[PID::EngineSpeed],
+            frame: 0,
+        };
+        assert_eq!(request.assemble(), Ok(vec![0x04]));
+
+        let mut request = ObdRequest {
+            mode: ObdMode::QueryPermanentDTC,
+            data: &[PID::EngineSpeed],
+            frame: 0,
+        };
+        assert_eq!(request.assemble(), Ok(vec![0x0A]));
+
+        let mut request = ObdRequest {
+            mode: ObdMode::QueryFreezeFrame,
+            data: &[PID::FreezeFrameDtc],
+            frame: 1,
+        };
+        assert_eq!(request.assemble(), Ok(vec![0x02, 0x02, 0x01]));
+
+        let mut request = ObdRequest {
+            mode: ObdMode::QueryNow,
+            data: &[PID::EngineSpeed; 7],
+            frame: 0,
+        };
+        assert_eq!(request.assemble(), Err(()));
+    }
+
+    #[test]
+    fn clear_challenge() {
+        let now = Instant::now();
+        let mut challenge = ClearChallenge::default();
+        assert!(challenge.confirm(0, now).is_none());
+
+        let nonce = challenge.issue(0x1234_5678, now);
+        assert!(challenge
+            .confirm(nonce, now + Duration::from_secs(1))
+            .is_some());
+        // Each challenge can only be used once
+        assert!(challenge.confirm(nonce, now).is_none());
+
+        challenge.issue(nonce, now);
+        assert!(challenge.confirm(nonce + 1, now).is_none());
+        assert!(challenge.confirm(nonce, now).is_none());
+
+        challenge.issue(nonce, now);
+        assert!(challenge
+            .confirm(
+                nonce,
+                now + ClearChallenge::TIMEOUT + Duration::from_millis(1)
+            )
+            .is_none());
+    }
+}
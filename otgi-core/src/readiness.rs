@@ -0,0 +1,234 @@
+/// OBD monitors; the discriminants identify them over BLE
+#[repr(u8)]
+#[derive(strum::FromRepr, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Monitor {
+    Misfire = 0,
+    FuelSystem = 1,
+    Components = 2,
+    Catalyst = 3,
+    HeatedCatalyst = 4,
+    EvaporativeSystem = 5,
+    SecondaryAirSystem = 6,
+    AcRefrigerant = 7,
+    OxygenSensor = 8,
+    OxygenSensorHeater = 9,
+    EgrVvtSystem = 10,
+    NmhcCatalyst = 11,
+    NoxScr = 12,
+    BoostPressure = 13,
+    ExhaustGasSensor = 14,
+    PmFilter = 15,
+}
+
+impl Monitor {
+    /// Continuous monitors run all the time, the rest need specific drive conditions
+    pub fn is_continuous(&self) -> bool {
+        matches!(self, Self::Misfire | Self::FuelSystem | Self::Components)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Ignition {
+    Spark,
+    Compression,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MonitorState {
+    pub monitor: Monitor,
+    pub available: bool,
+    pub complete: bool,
+}
+
+/// PID 01: monitor status since DTCs were cleared
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MonitorStatus {
+    pub mil: bool,
+    pub dtc_count: u8,
+    pub ignition: Ignition,
+    pub monitors: Vec<MonitorState>,
+}
+
+// Non-continuous monitors by bit of C (available) and D (incomplete)
+const SPARK_MONITORS: [Option<Monitor>; 8] = [
+    Some(Monitor::Catalyst),
+    Some(Monitor::HeatedCatalyst),
+    Some(Monitor::EvaporativeSystem),
+    Some(Monitor::SecondaryAirSystem),
+    Some(Monitor::AcRefrigerant),
+    Some(Monitor::OxygenSensor),
+    Some(Monitor::OxygenSensorHeater),
+    Some(Monitor::EgrVvtSystem),
+];
+const COMPRESSION_MONITORS: [Option<Monitor>; 8] = [
+    Some(Monitor::NmhcCatalyst),
+    Some(Monitor::NoxScr),
+    None,
+    Some(Monitor::BoostPressure),
+    None,
+    Some(Monitor::ExhaustGasSensor),
+    Some(Monitor::PmFilter),
+    Some(Monitor::EgrVvtSystem),
+];
+
+impl MonitorStatus {
+    pub(crate) fn decode(data: &[u8]) -> Result<Self, ()> {
+        if data.len() < 4 {
+            return Err(());
+        }
+        let [a, b, c, d] = [data[0], data[1], data[2], data[3]];
+
+        let ignition = if b & 0x08 == 0 {
+            Ignition::Spark
+        } else {
+            Ignition::Compression
+        };
+        let non_continuous = match ignition {
+            Ignition::Spark => SPARK_MONITORS,
+            Ignition::Compression => COMPRESSION_MONITORS,
+        };
+
+        // B holds the continuous monitors' available bits in 0-2 and incomplete bits in 4-6
+        let continuous = [Monitor::Misfire, Monitor::FuelSystem, Monitor::Components]
+            .into_iter()
+            .enumerate()
+            .map(|(bit, monitor)| MonitorState {
+                monitor,
+                available: b & (1 << bit) != 0,
+                complete: b & (1 << (bit + 4)) == 0,
+            });
+        let non_continuous = non_continuous
+            .into_iter()
+            .enumerate()
+            .filter_map(|(bit, monitor)| {
+                Some(MonitorState {
+                    monitor: monitor?,
+                    available: c & (1 << bit) != 0,
+                    complete: d & (1 << bit) == 0,
+                })
+            });
+
+        Ok(Self {
+            mil: a & 0x80 != 0,
+            dtc_count: a & 0x7F,
+            ignition,
+            monitors: continuous.chain(non_continuous).collect(),
+        })
+    }
+
+    /// Whether every monitor the vehicle has has completed, as an inspection would check
+    pub fn ready(&self) -> bool {
+        self.monitors.iter().all(|m| !m.available || m.complete)
+    }
+
+    pub fn incomplete(&self) -> impl Iterator<Item = Monitor> + '_ {
+        self.monitors
+            .iter()
+            .filter(|m| m.available && !m.complete)
+            .map(|m| m.monitor)
+    }
+
+    /// Compact form sent over BLE: flags (bit 0 MIL, bit 1 compression ignition, bit 2 ready),
+    /// the DTC count, then little endian bitmasks of available and complete monitors indexed by
+    /// [`Monitor`]
+    pub fn to_bytes(&self) -> [u8; 6] {
+        let mut flags = 0;
+        if self.mil {
+            flags |= 0x01;
+        }
+        if self.ignition == Ignition::Compression {
+            flags |= 0x02;
+        }
+        if self.ready() {
+            flags |= 0x04;
+        }
+
+        let mask = |f: fn(&MonitorState) -> bool| {
+            self.monitors
+                .iter()
+                .filter(|m| f(m))
+                .fold(0u16, |mask, m| mask | 1 << m.monitor as u8)
+        };
+        let available = mask(|m| m.available).to_le_bytes();
+        let complete = mask(|m| m.complete).to_le_bytes();
+
+        [
+            flags,
+            self.dtc_count,
+            available[0],
+            available[1],
+            complete[0],
+            complete[1],
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(monitor: Monitor, available: bool, complete: bool) -> MonitorState {
+        MonitorState {
+            monitor,
+            available,
+            complete,
+        }
+    }
+
+    #[test]
+    fn decodes_spark_ignition() {
+        // MIL on with 2 codes, misfire and fuel system available with fuel incomplete, catalyst,
+        // evap and O2 sensor available with evap incomplete
+        let status = MonitorStatus::decode(&[0x82, 0x23, 0x25, 0x04]).unwrap();
+
+        assert!(status.mil);
+        assert_eq!(status.dtc_count, 2);
+        assert_eq!(status.ignition, Ignition::Spark);
+        assert_eq!(status.monitors.len(), 11);
+        assert_eq!(status.monitors[0], state(Monitor::Misfire, true, true));
+        assert_eq!(status.monitors[1], state(Monitor::FuelSystem, true, false));
+        assert_eq!(status.monitors[2], state(Monitor::Components, false, true));
+        assert_eq!(status.monitors[3], state(Monitor::Catalyst, true, true));
+        assert_eq!(
+            status.monitors[5],
+            state(Monitor::EvaporativeSystem, true, false)
+        );
+        assert_eq!(status.monitors[8], state(Monitor::OxygenSensor, true, true));
+        assert!(status.monitors[3..]
+            .iter()
+            .all(|m| !m.monitor.is_continuous()));
+
+        assert!(!status.ready());
+        assert_eq!(
+            status.incomplete().collect::<Vec<_>>(),
+            [Monitor::FuelSystem, Monitor::EvaporativeSystem]
+        );
+    }
+
+    #[test]
+    fn decodes_compression_ignition() {
+        let status = MonitorStatus::decode(&[0x00, 0x0F, 0xFF, 0x00]).unwrap();
+
+        assert!(!status.mil);
+        assert_eq!(status.dtc_count, 0);
+        assert_eq!(status.ignition, Ignition::Compression);
+        // Bits 2 and 4 are reserved
+        assert_eq!(status.monitors.len(), 9);
+        assert_eq!(status.monitors[3], state(Monitor::NmhcCatalyst, true, true));
+        assert_eq!(status.monitors[8], state(Monitor::EgrVvtSystem, true, true));
+        assert!(status.ready());
+
+        assert!(MonitorStatus::decode(&[0x00, 0x0F, 0xFF]).is_err());
+    }
+
+    #[test]
+    fn encodes_for_ble() {
+        let status = MonitorStatus::decode(&[0x82, 0x23, 0x25, 0x04]).unwrap();
+        // Available: misfire, fuel, catalyst, evap, O2 sensor
+        // Complete: everything but fuel and evap
+        assert_eq!(status.to_bytes(), [0x01, 0x02, 0x2B, 0x01, 0xDD, 0x07]);
+
+        let status = MonitorStatus::decode(&[0x00, 0x08, 0x00, 0x00]).unwrap();
+        assert_eq!(status.to_bytes()[0], 0x06);
+    }
+}
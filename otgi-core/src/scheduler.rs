@@ -0,0 +1,376 @@
+use crate::{
+    can::CanTransport,
+    obd::{ObdDriver, ObdError, ObdQuery, ObdReadableData, PID},
+    units::Quantity,
+};
+use std::time::{Duration, Instant};
+
+/// A sample older than this many periods of its PID is stale
+const STALE_PERIODS: u32 = 3;
+/// Weight of the newest interval in the achieved rate's moving average
+const RATE_SMOOTHING: f32 = 0.2;
+/// Most PIDs a single mode 01 request may carry
+const MAX_PIDS_PER_REQUEST: usize = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub value: Quantity,
+    pub at: Instant,
+}
+
+/// How well a PID is keeping up with its target rate
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PollStats {
+    pub target_hz: f32,
+    /// Smoothed rate of successful samples, `None` until two have arrived
+    pub achieved_hz: Option<f32>,
+    pub samples: u32,
+    pub failures: u32,
+}
+
+struct Entry {
+    pid: PID,
+    period: Duration,
+    priority: Priority,
+    last_polled: Option<Instant>,
+    latest: Option<Sample>,
+    // Moving average of the time between samples, in seconds
+    interval: Option<f32>,
+    samples: u32,
+    failures: u32,
+}
+
+impl Entry {
+    fn due_at(&self) -> Option<Instant> {
+        self.last_polled.map(|polled| polled + self.period)
+    }
+}
+
+type Subscriber = Box<dyn FnMut(PID, &Sample)>;
+
+/// Polls registered mode 01 PIDs at their target rates, highest priority first, batching
+/// whatever is due into multi-PID requests
+pub struct Scheduler {
+    entries: Vec<Entry>,
+    min_gap: Duration,
+    last_request: Option<Instant>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl Scheduler {
+    /// `min_gap` is the least time left between two requests
+    pub fn new(min_gap: Duration) -> Self {
+        Self {
+            entries: vec![],
+            min_gap,
+            last_request: None,
+            subscribers: vec![],
+        }
+    }
+
+    /// Polls `pid` at `rate_hz`, replacing any earlier registration. Only PIDs that decode to a
+    /// value can be scheduled.
+    pub fn register(&mut self, pid: PID, rate_hz: f32, priority: Priority) {
+        assert!(pid.has_value(), "{:?} doesn't decode to a value", pid);
+        assert!(rate_hz > 0.0, "rate must be positive");
+
+        self.unregister(pid);
+        self.entries.push(Entry {
+            pid,
+            period: Duration::from_secs_f64(1.0 / rate_hz as f64),
+            priority,
+            last_polled: None,
+            latest: None,
+            interval: None,
+            samples: 0,
+            failures: 0,
+        });
+    }
+
+    pub fn unregister(&mut self, pid: PID) {
+        self.entries.retain(|entry| entry.pid != pid);
+    }
+
+    /// Calls `subscriber` with every new sample
+    pub fn subscribe(&mut self, subscriber: impl FnMut(PID, &Sample) + 'static) {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    pub fn latest(&self, pid: PID) -> Option<&Sample> {
+        self.entry(pid)?.latest.as_ref()
+    }
+
+    /// Whether `pid` has gone without a sample for several of its periods
+    pub fn is_stale(&self, pid: PID, now: Instant) -> bool {
+        match self.entry(pid) {
+            Some(Entry {
+                latest: Some(sample),
+                period,
+                ..
+            }) => now.saturating_duration_since(sample.at) > *period * STALE_PERIODS,
+            _ => true,
+        }
+    }
+
+    pub fn stats(&self, pid: PID) -> Option<PollStats> {
+        let entry = self.entry(pid)?;
+        Some(PollStats {
+            target_hz: 1.0 / entry.period.as_secs_f32(),
+            achieved_hz: entry.interval.map(|interval| 1.0 / interval),
+            samples: entry.samples,
+            failures: entry.failures,
+        })
+    }
+
+    /// When the next request can be sent, or `None` if nothing is registered
+    pub fn next_poll(&self) -> Option<Instant> {
+        let due = self.entries.iter().map(|entry| entry.due_at()).min()?;
+        let earliest = self.last_request.map(|last| last + self.min_gap);
+        match (due, earliest) {
+            (Some(due), Some(earliest)) => Some(due.max(earliest)),
+            (due, earliest) => due.or(earliest).or(Some(Instant::now())),
+        }
+    }
+
+    /// Sends one request for the PIDs that are due, returning the PIDs asked for. Nothing is sent
+    /// within the minimum gap of the previous request or when nothing is due. The request goes to
+    /// every ECU, so PIDs owned by different ECUs are answered together.
+    pub fn poll<T: CanTransport>(
+        &mut self,
+        driver: &mut ObdDriver<T>,
+        now: Instant,
+    ) -> Result<Vec<PID>, ObdError<T::Error>> {
+        if self
+            .last_request
+            .is_some_and(|last| now < last + self.min_gap)
+        {
+            return Ok(vec![]);
+        }
+
+        // Most important first, then whichever has waited longest past its due time
+        let mut due: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.due_at().map_or(true, |due| due <= now))
+            .filter(|entry| driver.is_supported(entry.pid))
+            .collect();
+        due.sort_by_key(|entry| (std::cmp::Reverse(entry.priority), entry.due_at()));
+        let pids: Vec<PID> = due
+            .iter()
+            .take(MAX_PIDS_PER_REQUEST)
+            .map(|entry| entry.pid)
+            .collect();
+        if pids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.last_request = Some(now);
+        for entry in self.entries.iter_mut().filter(|e| pids.contains(&e.pid)) {
+            entry.last_polled = Some(now);
+        }
+
+        let query = ObdQuery::multiple(&pids).unwrap();
+        let values: Vec<(PID, Quantity)> = match driver.query(&query) {
+            Ok(ObdReadableData::Values(values)) => values
+                .into_iter()
+                .filter_map(|(pid, data)| match data {
+                    ObdReadableData::Value(value) => Some((pid, value)),
+                    _ => None,
+                })
+                .collect(),
+            Ok(_) => vec![],
+            Err(e) => {
+                self.record_failures(&pids, &[]);
+                return Err(e);
+            }
+        };
+
+        for &(pid, value) in &values {
+            let sample = Sample { value, at: now };
+            let entry = self.entry_mut(pid).unwrap();
+            if let Some(previous) = entry.latest {
+                let interval = now.saturating_duration_since(previous.at).as_secs_f32();
+                entry.interval = Some(match entry.interval {
+                    Some(average) => average + RATE_SMOOTHING * (interval - average),
+                    None => interval,
+                });
+            }
+            entry.latest = Some(sample);
+            entry.samples += 1;
+
+            for subscriber in &mut self.subscribers {
+                subscriber(pid, &sample);
+            }
+        }
+        let answered: Vec<PID> = values.iter().map(|&(pid, _)| pid).collect();
+        self.record_failures(&pids, &answered);
+
+        Ok(pids)
+    }
+
+    fn record_failures(&mut self, requested: &[PID], answered: &[PID]) {
+        for entry in self.entries.iter_mut() {
+            if requested.contains(&entry.pid) && !answered.contains(&entry.pid) {
+                entry.failures += 1;
+            }
+        }
+    }
+
+    fn entry(&self, pid: PID) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.pid == pid)
+    }
+
+    fn entry_mut(&mut self, pid: PID) -> Option<&mut Entry> {
+        self.entries.iter_mut().find(|entry| entry.pid == pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        can::{CanFrame, CanId},
+        mock::MockTransport,
+        sim::{EcuSimulator, Scenario, SimTransport},
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    fn driver() -> ObdDriver<SimTransport> {
+        let scenario: Scenario = "0 EngineSpeed 800\n0 VehicleSpeed 30\n0 MassAirFlow 5\n"
+            .parse()
+            .unwrap();
+        ObdDriver::try_new(
+            SimTransport::new(EcuSimulator::new(scenario)),
+            &Default::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn polls_at_target_rates() {
+        let mut driver = driver();
+        let mut scheduler = Scheduler::new(Duration::from_millis(50));
+        scheduler.register(PID::EngineSpeed, 10.0, Priority::High);
+        scheduler.register(PID::VehicleSpeed, 2.0, Priority::Low);
+
+        let start = Instant::now();
+        // Everything is due at first and goes out in one request
+        assert_eq!(
+            scheduler.poll(&mut driver, start).unwrap(),
+            [PID::EngineSpeed, PID::VehicleSpeed]
+        );
+        // Within the minimum gap nothing is sent
+        assert!(scheduler
+            .poll(&mut driver, start + Duration::from_millis(20))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            scheduler.next_poll(),
+            Some(start + Duration::from_millis(100))
+        );
+
+        let mut polls = vec![];
+        for ms in (100..=1000).step_by(50) {
+            polls.extend(
+                scheduler
+                    .poll(&mut driver, start + Duration::from_millis(ms))
+                    .unwrap(),
+            );
+        }
+        let count = |pid| polls.iter().filter(|&&p| p == pid).count();
+        assert_eq!(count(PID::EngineSpeed), 10);
+        assert_eq!(count(PID::VehicleSpeed), 2);
+
+        let stats = scheduler.stats(PID::EngineSpeed).unwrap();
+        assert_eq!(stats.target_hz, 10.0);
+        assert!((stats.achieved_hz.unwrap() - 10.0).abs() < 0.1);
+        assert_eq!(stats.samples, 11);
+        assert_eq!(stats.failures, 0);
+        assert_eq!(
+            scheduler.latest(PID::VehicleSpeed).unwrap().value.value,
+            30.0
+        );
+    }
+
+    #[test]
+    fn high_priority_goes_first() {
+        let mut driver = driver();
+        let mut scheduler = Scheduler::new(Duration::ZERO);
+        let low = [
+            PID::ThrottlePosition,
+            PID::RunTime,
+            PID::FuelTankLevelInput,
+            PID::Odometer,
+            PID::CalculatedEngineLoad,
+            PID::EngineCoolantTemperature,
+        ];
+        for pid in low {
+            scheduler.register(pid, 1.0, Priority::Low);
+        }
+        scheduler.register(PID::MassAirFlow, 1.0, Priority::High);
+
+        let polled = scheduler.poll(&mut driver, Instant::now()).unwrap();
+        assert_eq!(polled.len(), 6);
+        assert_eq!(polled[0], PID::MassAirFlow);
+        // The scenario only has MAF, so the rest fail
+        assert_eq!(scheduler.stats(PID::MassAirFlow).unwrap().failures, 0);
+        assert_eq!(scheduler.stats(PID::ThrottlePosition).unwrap().failures, 1);
+    }
+
+    #[test]
+    fn notifies_subscribers_and_tracks_staleness() {
+        let mut driver = driver();
+        let mut scheduler = Scheduler::new(Duration::ZERO);
+        scheduler.register(PID::EngineSpeed, 4.0, Priority::Normal);
+
+        let seen = Rc::new(RefCell::new(vec![]));
+        let sink = seen.clone();
+        scheduler.subscribe(move |pid, sample| sink.borrow_mut().push((pid, sample.value.value)));
+
+        let start = Instant::now();
+        assert!(scheduler.is_stale(PID::EngineSpeed, start));
+        scheduler.poll(&mut driver, start).unwrap();
+        assert_eq!(*seen.borrow(), [(PID::EngineSpeed, 800.0)]);
+
+        assert!(!scheduler.is_stale(PID::EngineSpeed, start + Duration::from_millis(750)));
+        assert!(scheduler.is_stale(PID::EngineSpeed, start + Duration::from_millis(751)));
+    }
+
+    #[test]
+    fn polls_pids_across_ecus() {
+        let frame = |id, data: &[u8]| CanFrame::new(CanId::Standard(id), data).unwrap();
+        let mut transport = MockTransport::new();
+        transport.expect(
+            frame(0x7DF, &[0x03, 0x01, 0x0C, 0x0D, 0, 0, 0, 0]),
+            [
+                frame(0x7E8, &[0x04, 0x41, 0x0C, 0x0C, 0x80, 0, 0, 0]),
+                frame(0x7E9, &[0x03, 0x41, 0x0D, 0x1E, 0, 0, 0, 0]),
+            ],
+        );
+        let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+        let mut scheduler = Scheduler::new(Duration::ZERO);
+        scheduler.register(PID::EngineSpeed, 1.0, Priority::Normal);
+        scheduler.register(PID::VehicleSpeed, 1.0, Priority::Normal);
+
+        let polled = scheduler.poll(&mut driver, Instant::now()).unwrap();
+        assert_eq!(polled, [PID::EngineSpeed, PID::VehicleSpeed]);
+        assert_eq!(
+            scheduler.latest(PID::EngineSpeed).unwrap().value.value,
+            800.0
+        );
+        assert_eq!(
+            scheduler.latest(PID::VehicleSpeed).unwrap().value.value,
+            30.0
+        );
+        assert_eq!(scheduler.stats(PID::EngineSpeed).unwrap().failures, 0);
+        assert_eq!(scheduler.stats(PID::VehicleSpeed).unwrap().failures, 0);
+        assert!(transport.is_done());
+    }
+}
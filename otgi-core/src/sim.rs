@@ -0,0 +1,644 @@
+//! A simulated engine ECU that answers OBD-II requests from a scenario of values over time.
+//!
+//! Scenarios are plain text with one entry per line:
+//!
+//! ```text
+//! # seconds  PID (name or hex)  value
+//! 0          EngineSpeed        800
+//! 10         EngineSpeed        2500
+//! 0          0x0D               0
+//! # seconds  dtc  codes stored from then on (none to clear)
+//! 30         dtc  P0301 P0420
+//! # seconds  pending  codes pending from then on (none to clear)
+//! 20         pending  P0301
+//! # seconds  vin  vehicle identification number
+//! 0          vin  1HGCM82633A004352
+//! ```
+//!
+//! Stored codes double as permanent codes, which survive a mode 04 clear.
+//!
+//! PID values are interpolated linearly between entries and hold their last value afterwards.
+
+use crate::{
+    can::{CanFilter, CanFrame, CanId, CanTransport},
+    dtc::DiagnosticTroubleCode,
+    isotp::{FlowStatus, IsoTpFrame},
+    obd::{ObdMode, PID},
+    vehicle_info::InfoType,
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+pub const FUNCTIONAL_REQUEST_ID: CanId = CanId::Standard(0x7DF);
+pub const REQUEST_ID: CanId = CanId::Standard(0x7E0);
+pub const RESPONSE_ID: CanId = CanId::Standard(0x7E8);
+
+pub const CALIBRATION_ID: &[u8; 16] = b"OTGI-SIM\0\0\0\0\0\0\0\0";
+pub const CALIBRATION_VERIFICATION_NUMBER: u32 = 0x4F54_4749;
+pub const ECU_NAME: &[u8; 20] = b"ECM\0-EngineControl\0\0";
+/// How long the non-continuous monitors take to complete again after codes are cleared
+pub const MONITOR_DRIVE_CYCLE: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScenarioError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    values: BTreeMap<u8, Vec<(f32, f32)>>,
+    dtcs: Vec<(f32, Vec<DiagnosticTroubleCode>)>,
+    pending: Vec<(f32, Vec<DiagnosticTroubleCode>)>,
+    vin: Option<String>,
+}
+
+impl Scenario {
+    /// Value of `pid` at `time`, if the scenario defines it at all
+    pub fn value(&self, pid: PID, time: Duration) -> Option<f32> {
+        let points = self.values.get(&(pid as u8))?;
+        let t = time.as_secs_f32();
+
+        let next = points.partition_point(|&(time, _)| time <= t);
+        if next == 0 {
+            return Some(points[0].1);
+        } else if next == points.len() {
+            return Some(points[next - 1].1);
+        }
+
+        let (t0, v0) = points[next - 1];
+        let (t1, v1) = points[next];
+        Some(v0 + (v1 - v0) * (t - t0) / (t1 - t0))
+    }
+
+    pub fn vin(&self) -> Option<&str> {
+        self.vin.as_deref()
+    }
+
+    pub fn pids(&self) -> impl Iterator<Item = PID> + '_ {
+        self.values.keys().filter_map(|&pid| PID::from_repr(pid))
+    }
+
+    /// Stored DTCs at `time` along with the time they were set
+    fn dtcs(&self, time: Duration) -> Option<(f32, &[DiagnosticTroubleCode])> {
+        latest(&self.dtcs, time)
+    }
+
+    /// Pending DTCs at `time` along with the time they were set
+    fn pending(&self, time: Duration) -> Option<(f32, &[DiagnosticTroubleCode])> {
+        latest(&self.pending, time)
+    }
+}
+
+fn latest(
+    entries: &[(f32, Vec<DiagnosticTroubleCode>)],
+    time: Duration,
+) -> Option<(f32, &[DiagnosticTroubleCode])> {
+    let t = time.as_secs_f32();
+    entries
+        .iter()
+        .rev()
+        .find(|(time, _)| *time <= t)
+        .map(|(time, codes)| (*time, codes.as_slice()))
+}
+
+impl FromStr for Scenario {
+    type Err = ScenarioError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scenario = Scenario::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let error = |message: &str| ScenarioError {
+                line: i + 1,
+                message: message.to_string(),
+            };
+
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let time: f32 = fields
+                .next()
+                .and_then(|t| t.parse().ok())
+                .filter(|t: &f32| *t >= 0.0)
+                .ok_or_else(|| error("expected a time in seconds"))?;
+            let key = fields
+                .next()
+                .ok_or_else(|| error("expected a PID, dtc, pending or vin"))?;
+
+            if key.eq_ignore_ascii_case("vin") {
+                let vin = fields
+                    .next()
+                    .filter(|vin| vin.len() == 17 && vin.is_ascii())
+                    .ok_or_else(|| error("expected a 17 character VIN"))?;
+                scenario.vin = Some(vin.to_string());
+                continue;
+            }
+
+            if key.eq_ignore_ascii_case("dtc") || key.eq_ignore_ascii_case("pending") {
+                let codes = fields
+                    .filter(|code| !code.eq_ignore_ascii_case("none"))
+                    .map(|code| code.parse().map_err(|_| error("invalid trouble code")))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if key.eq_ignore_ascii_case("dtc") {
+                    scenario.dtcs.push((time, codes));
+                } else {
+                    scenario.pending.push((time, codes));
+                }
+                continue;
+            }
+
+            let pid = match key.strip_prefix("0x") {
+                Some(hex) => u8::from_str_radix(hex, 16).ok().and_then(PID::from_repr),
+                None => PID::from_str(key).ok(),
+            }
+            .ok_or_else(|| error("unknown PID"))?;
+
+            if !pid.has_value() {
+                return Err(error(
+                    "support bitmaps, monitor status and freeze frames are derived from the scenario",
+                ));
+            }
+
+            let value: f32 = fields
+                .next()
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| error("expected a value"))?;
+
+            scenario
+                .values
+                .entry(pid as u8)
+                .or_default()
+                .push((time, value));
+        }
+
+        for points in scenario.values.values_mut() {
+            points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+        scenario.dtcs.sort_by(|a, b| a.0.total_cmp(&b.0));
+        scenario.pending.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(scenario)
+    }
+}
+
+/// Inverse of each PID's [`PID::formula`], worked out by hand so the simulator checks the
+/// formulas rather than repeating them
+fn encode(pid: PID, value: f32) -> Vec<u8> {
+    let (raw, len) = match pid {
+        PID::EngineSpeed => (value * 4.0, 2),
+        PID::VehicleSpeed
+        | PID::IntakeManifoldAbsolutePressure
+        | PID::WarmUpsSinceCodesCleared
+        | PID::AbsoluteBarometricPressure => (value, 1),
+        PID::ThrottlePosition
+        | PID::FuelTankLevelInput
+        | PID::RelativeThrottlePosition
+        | PID::CalculatedEngineLoad
+        | PID::CommandedEgr
+        | PID::CommandedEvaporativePurge
+        | PID::AbsoluteThrottlePositionB
+        | PID::AbsoluteThrottlePositionC
+        | PID::AcceleratorPedalPositionD
+        | PID::AcceleratorPedalPositionE
+        | PID::AcceleratorPedalPositionF
+        | PID::CommandedThrottleActuator
+        | PID::EthanolFuelPercentage
+        | PID::RelativeAcceleratorPedalPosition
+        | PID::HybridBatteryRemainingLife => (value * 2.55, 1),
+        PID::RunTime
+        | PID::DistanceWithMilOn
+        | PID::DistanceSinceCodesCleared
+        | PID::TimeRunWithMilOn
+        | PID::TimeSinceCodesCleared
+        | PID::EngineReferenceTorque => (value, 2),
+        PID::EngineFuelRate => (value * 20.0, 2),
+        PID::Odometer => (value * 10.0, 4),
+        PID::MassAirFlow => (value * 100.0, 2),
+        PID::ShortTermFuelTrimBankOne
+        | PID::LongTermFuelTrimBankOne
+        | PID::ShortTermFuelTrimBankTwo
+        | PID::LongTermFuelTrimBankTwo
+        | PID::EgrError => ((value + 100.0) * 1.28, 1),
+        PID::EngineCoolantTemperature
+        | PID::IntakeAirTemperature
+        | PID::AmbientAirTemperature
+        | PID::EngineOilTemperature => (value + 40.0, 1),
+        PID::FuelPressure => (value / 3.0, 1),
+        PID::TimingAdvance => ((value + 64.0) * 2.0, 1),
+        PID::OxygenSensorOneVoltage
+        | PID::OxygenSensorTwoVoltage
+        | PID::OxygenSensorThreeVoltage
+        | PID::OxygenSensorFourVoltage
+        | PID::OxygenSensorFiveVoltage
+        | PID::OxygenSensorSixVoltage
+        | PID::OxygenSensorSevenVoltage
+        | PID::OxygenSensorEightVoltage => {
+            // Sensor not used for trim
+            let mut data = scale(value * 200.0, 1);
+            data.push(0xFF);
+            return data;
+        }
+        PID::FuelRailPressure => (value / 0.079, 2),
+        PID::FuelRailGaugePressure | PID::FuelRailAbsolutePressure => (value / 10.0, 2),
+        PID::WideRangeOxygenSensorOne
+        | PID::WideRangeOxygenSensorTwo
+        | PID::WideRangeOxygenSensorThree
+        | PID::WideRangeOxygenSensorFour
+        | PID::WideRangeOxygenSensorFive
+        | PID::WideRangeOxygenSensorSix
+        | PID::WideRangeOxygenSensorSeven
+        | PID::WideRangeOxygenSensorEight => {
+            // Sensor voltage isn't simulated
+            let mut data = scale(value * 32768.0, 2);
+            data.extend([0, 0]);
+            return data;
+        }
+        PID::CommandedAirFuelEquivalenceRatio => (value * 32768.0, 2),
+        PID::EvapSystemVaporPressure => {
+            let raw = (value * 4.0)
+                .round()
+                .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            return raw.to_be_bytes().to_vec();
+        }
+        PID::CatalystTemperatureBankOneSensorOne
+        | PID::CatalystTemperatureBankTwoSensorOne
+        | PID::CatalystTemperatureBankOneSensorTwo
+        | PID::CatalystTemperatureBankTwoSensorTwo => ((value + 40.0) * 10.0, 2),
+        PID::ControlModuleVoltage => (value * 1000.0, 2),
+        PID::AbsoluteLoadValue => (value * 2.55, 2),
+        PID::MaximumMassAirFlow => {
+            // B to D are reserved
+            let mut data = scale(value / 10.0, 1);
+            data.extend([0, 0, 0]);
+            return data;
+        }
+        PID::AbsoluteEvapSystemVaporPressure => (value * 200.0, 2),
+        PID::WideEvapSystemVaporPressure => (value + 32767.0, 2),
+        PID::FuelInjectionTiming => ((value + 210.0) * 128.0, 2),
+        PID::DriverDemandEngineTorque | PID::ActualEngineTorque => (value + 125.0, 1),
+        _ => unreachable!("support bitmaps and freeze frame DTCs have no value"),
+    };
+
+    scale(raw, len)
+}
+
+/// Rounds `raw` into a `len` byte big endian unsigned integer, saturating at its limits
+fn scale(raw: f32, len: usize) -> Vec<u8> {
+    let max = (1u64 << (8 * len)) - 1;
+    let raw = (raw.round().max(0.0) as u64).min(max);
+    raw.to_be_bytes()[8 - len..].to_vec()
+}
+
+pub struct EcuSimulator {
+    scenario: Scenario,
+    cleared_at: Option<Duration>,
+    padding: u8,
+    // Consecutive frames of a multi-frame response waiting on flow control
+    pending: VecDeque<[u8; 8]>,
+}
+
+impl EcuSimulator {
+    pub fn new(scenario: Scenario) -> Self {
+        Self {
+            scenario,
+            cleared_at: None,
+            padding: 0x00,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    /// Handles a frame seen on the bus at `now` (relative to the scenario start) and returns the
+    /// frames the ECU answers with
+    pub fn handle(&mut self, frame: &CanFrame, now: Duration) -> Vec<CanFrame> {
+        if frame.id() != FUNCTIONAL_REQUEST_ID && frame.id() != REQUEST_ID {
+            return vec![];
+        }
+
+        let response = match IsoTpFrame::parse::<()>(frame.data()) {
+            Ok(IsoTpFrame::Single { data }) => self.respond(data, now),
+            Ok(IsoTpFrame::FlowControl {
+                status: FlowStatus::ContinueToSend,
+                block_size,
+                ..
+            }) if frame.id() == REQUEST_ID => {
+                let count = match block_size {
+                    0 => self.pending.len(),
+                    n => n as usize,
+                };
+                return self
+                    .pending
+                    .drain(..count.min(self.pending.len()))
+                    .map(|data| CanFrame::new(RESPONSE_ID, &data).unwrap())
+                    .collect();
+            }
+            _ => return vec![],
+        };
+
+        let Some(payload) = response else {
+            return vec![];
+        };
+
+        if payload.len() <= 7 {
+            let data = IsoTpFrame::Single { data: &payload }.encode(self.padding);
+            return vec![CanFrame::new(RESPONSE_ID, &data).unwrap()];
+        }
+
+        let first = IsoTpFrame::First {
+            len: payload.len(),
+            data: &payload[..6],
+        }
+        .encode(self.padding);
+        self.pending = payload[6..]
+            .chunks(7)
+            .zip((1..=0x0F).chain(0..=0x0F).cycle())
+            .map(|(data, sequence)| IsoTpFrame::Consecutive { sequence, data }.encode(self.padding))
+            .collect();
+
+        vec![CanFrame::new(RESPONSE_ID, &first).unwrap()]
+    }
+
+    fn stored_dtcs(&self, now: Duration) -> Option<(f32, &[DiagnosticTroubleCode])> {
+        self.scenario
+            .dtcs(now)
+            .filter(|(set, _)| self.cleared_at.map_or(true, |c| c.as_secs_f32() < *set))
+    }
+
+    fn pending_dtcs(&self, now: Duration) -> Option<(f32, &[DiagnosticTroubleCode])> {
+        self.scenario
+            .pending(now)
+            .filter(|(set, _)| self.cleared_at.map_or(true, |c| c.as_secs_f32() < *set))
+    }
+
+    /// PIDs answered in mode 01; the monitor status is always available
+    fn pids(&self) -> impl Iterator<Item = PID> + '_ {
+        self.scenario.pids().chain([PID::MonitorStatus])
+    }
+
+    /// PID 01 for a spark ignition engine with all continuous monitors and the catalyst, EVAP, O2
+    /// sensor, O2 heater and EGR monitors. Clearing codes resets the non-continuous monitors until
+    /// they have run for a drive cycle.
+    fn monitor_status(&self, time: Duration) -> [u8; 4] {
+        let codes = self
+            .stored_dtcs(time)
+            .map_or(0, |(_, codes)| codes.len() as u8);
+        let mil = if codes > 0 { 0x80 } else { 0x00 };
+        let available = 0xE5;
+        let incomplete = match self.cleared_at {
+            Some(cleared) if time < cleared + MONITOR_DRIVE_CYCLE => available,
+            _ => 0x00,
+        };
+        [mil | codes.min(0x7F), 0x07, available, incomplete]
+    }
+
+    fn supported(&self, range: u8) -> [u8; 4] {
+        let range = range as u16;
+        let mut bitmap = self
+            .pids()
+            .map(|pid| pid as u16)
+            .filter(|&pid| pid > range && pid <= range + 0x20)
+            .fold(0u32, |bitmap, pid| bitmap | 1 << (0x20 - (pid - range)));
+
+        // The last bit of each bitmap advertises whether the next one is available
+        if self.pids().any(|pid| pid as u16 > range + 0x20) {
+            bitmap |= 1;
+        }
+        bitmap.to_be_bytes()
+    }
+
+    fn pid_data(&self, pid: u8, time: Duration) -> Option<Vec<u8>> {
+        match PID::from_repr(pid)? {
+            pid if pid.is_capability() => {
+                // Later bitmaps are only answered if the previous one advertised them
+                if pid != PID::FirstCap && self.supported(pid as u8 - 0x20)[3] & 1 == 0 {
+                    return None;
+                }
+                Some(self.supported(pid as u8).to_vec())
+            }
+            PID::MonitorStatus => Some(self.monitor_status(time).to_vec()),
+            pid => Some(encode(pid, self.scenario.value(pid, time)?)),
+        }
+    }
+
+    fn respond(&mut self, request: &[u8], now: Duration) -> Option<Vec<u8>> {
+        let mode = ObdMode::from_repr(*request.first()?)?;
+        let mut response = vec![0x40 + mode as u8];
+
+        match mode {
+            ObdMode::QueryNow => {
+                // Unsupported PIDs are left out, and nothing is sent if none are supported
+                for &pid in &request[1..] {
+                    if let Some(data) = self.pid_data(pid, now) {
+                        response.push(pid);
+                        response.extend(data);
+                    }
+                }
+                if response.len() == 1 {
+                    return None;
+                }
+            }
+            ObdMode::QueryFreezeFrame => {
+                let pid = *request.get(1)?;
+                let frame = request.get(2).copied().unwrap_or(0);
+                let stored = self.stored_dtcs(now).filter(|(_, c)| !c.is_empty());
+                if frame != 0 {
+                    return None;
+                }
+
+                response.extend([pid, frame]);
+                match stored {
+                    // PID 02 reports the DTC that caused the freeze frame to be stored, 0000 if
+                    // there is none
+                    _ if pid == PID::FreezeFrameDtc as u8 => {
+                        response.extend(stored.map_or([0, 0], |(_, codes)| codes[0].to_bytes()))
+                    }
+                    Some((set, _)) => {
+                        response.extend(self.pid_data(pid, Duration::from_secs_f32(set))?)
+                    }
+                    None => return None,
+                }
+            }
+            ObdMode::QueryDTC | ObdMode::QueryPendingDTC | ObdMode::QueryPermanentDTC => {
+                let codes = match mode {
+                    ObdMode::QueryDTC => self.stored_dtcs(now),
+                    ObdMode::QueryPendingDTC => self.pending_dtcs(now),
+                    // Permanent codes can't be cleared
+                    _ => self.scenario.dtcs(now),
+                }
+                .map_or(&[][..], |(_, codes)| codes);
+                response.push(codes.len() as u8);
+                response.extend(codes.iter().flat_map(|code| code.to_bytes()));
+            }
+            ObdMode::ClearDTC => {
+                self.cleared_at = Some(now);
+            }
+            ObdMode::VehicleInformation => {
+                let info = *request.get(1)?;
+                response.push(info);
+                if info != InfoType::Supported as u8 {
+                    // Every simulated InfoType holds a single item
+                    response.push(0x01);
+                }
+
+                match InfoType::from_repr(info)? {
+                    InfoType::Supported => {
+                        let mut bitmap: u32 = 1 << (0x20 - 0x04) | 1 << (0x20 - 0x06);
+                        bitmap |= 1 << (0x20 - 0x0A);
+                        if self.scenario.vin.is_some() {
+                            bitmap |= 1 << (0x20 - 0x02);
+                        }
+                        response.extend(bitmap.to_be_bytes());
+                    }
+                    InfoType::Vin => response.extend(self.scenario.vin.as_ref()?.bytes()),
+                    InfoType::CalibrationIds => response.extend(CALIBRATION_ID),
+                    InfoType::CalibrationVerificationNumbers => {
+                        response.extend(CALIBRATION_VERIFICATION_NUMBER.to_be_bytes())
+                    }
+                    InfoType::EcuName => response.extend(ECU_NAME),
+                    _ => return None,
+                }
+            }
+        }
+
+        Some(response)
+    }
+
+    /// Serves requests on `transport` until `until` passes, or forever when it is `None`
+    pub fn run<T: CanTransport>(
+        &mut self,
+        transport: &mut T,
+        until: Option<Instant>,
+    ) -> Result<(), T::Error> {
+        transport.set_filter(CanFilter {
+            id: 0x7D0,
+            mask: 0x7F0,
+            extended: false,
+        })?;
+
+        let start = Instant::now();
+        while until.map_or(true, |until| Instant::now() < until) {
+            let Some(frame) = transport.receive(Duration::from_millis(100))? else {
+                continue;
+            };
+
+            for response in self.handle(&frame, start.elapsed()) {
+                transport.transmit(&response, Duration::from_millis(100))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// [`CanTransport`] connected directly to an [`EcuSimulator`], with a manually advanced clock
+pub struct SimTransport {
+    ecu: EcuSimulator,
+    rx: VecDeque<CanFrame>,
+    filter: CanFilter,
+    elapsed: Duration,
+}
+
+impl SimTransport {
+    pub fn new(ecu: EcuSimulator) -> Self {
+        Self {
+            ecu,
+            rx: VecDeque::new(),
+            filter: CanFilter::accept_all(),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn advance(&mut self, time: Duration) {
+        self.elapsed += time;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn ecu(&self) -> &EcuSimulator {
+        &self.ecu
+    }
+}
+
+impl CanTransport for SimTransport {
+    type Error = core::convert::Infallible;
+
+    fn transmit(&mut self, frame: &CanFrame, _timeout: Duration) -> Result<(), Self::Error> {
+        self.rx.extend(self.ecu.handle(frame, self.elapsed));
+        Ok(())
+    }
+
+    fn receive(&mut self, _timeout: Duration) -> Result<Option<CanFrame>, Self::Error> {
+        while let Some(frame) = self.rx.pop_front() {
+            if self.filter.matches(frame.id()) {
+                return Ok(Some(frame));
+            }
+        }
+        Ok(None)
+    }
+
+    fn set_filter(&mut self, filter: CanFilter) -> Result<(), Self::Error> {
+        self.filter = filter;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_values() {
+        let scenario: Scenario = "0 EngineSpeed 800\n10 EngineSpeed 1800\n".parse().unwrap();
+
+        assert_eq!(
+            scenario.value(PID::EngineSpeed, Duration::ZERO),
+            Some(800.0)
+        );
+        assert_eq!(
+            scenario.value(PID::EngineSpeed, Duration::from_secs(5)),
+            Some(1300.0)
+        );
+        assert_eq!(
+            scenario.value(PID::EngineSpeed, Duration::from_secs(60)),
+            Some(1800.0)
+        );
+        assert_eq!(scenario.value(PID::VehicleSpeed, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let err = "0 EngineSpeed 800\n1 NotAPid 3\n"
+            .parse::<Scenario>()
+            .unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!("x EngineSpeed 800".parse::<Scenario>().is_err());
+        assert!("0 EngineSpeed".parse::<Scenario>().is_err());
+        assert!("0 dtc P9999".parse::<Scenario>().is_err());
+        assert!("0 FirstCap 1".parse::<Scenario>().is_err());
+        assert!("0 MonitorStatus 1".parse::<Scenario>().is_err());
+    }
+
+    #[test]
+    fn advertises_supported_pids() {
+        let scenario: Scenario = "0 0x0C 800\n0 Odometer 5\n".parse().unwrap();
+        let ecu = EcuSimulator::new(scenario);
+
+        // 0x01, 0x0C and the next range, 0x20
+        assert_eq!(ecu.supported(0x00), [0x80, 0x10, 0x00, 0x01]);
+        // Nothing in 0x21-0x40 but 0xA6 is further along
+        assert_eq!(ecu.supported(0x20), [0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(ecu.supported(0xA0), [0x04, 0x00, 0x00, 0x00]);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::can::{CanFilter, CanFrame, CanId, CanTransport};
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+/// A frame along with when it was seen, relative to the start of the capture
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CapturedFrame {
+    pub timestamp: Duration,
+    pub frame: CanFrame,
+}
+
+impl CapturedFrame {
+    /// One line of a `candump -l` log, e.g. `(12.345678) can0 7E8#04410C1AF8`, which can be
+    /// replayed with `canplayer` or read by most CAN analysis tools
+    pub fn to_candump(&self, interface: &str) -> String {
+        let mut line = format!(
+            "({}.{:06}) {} ",
+            self.timestamp.as_secs(),
+            self.timestamp.subsec_micros(),
+            interface
+        );
+        match self.frame.id() {
+            CanId::Standard(id) => write!(line, "{:03X}#", id),
+            CanId::Extended(id) => write!(line, "{:08X}#", id),
+        }
+        .unwrap();
+        for byte in self.frame.data() {
+            write!(line, "{:02X}", byte).unwrap();
+        }
+        line
+    }
+}
+
+/// Records traffic without ever transmitting, keeping frames that pass any of its filters. For
+/// the controller not to acknowledge frames either, the transport has to be opened in its
+/// listen-only mode.
+pub struct Sniffer<T: CanTransport> {
+    transport: T,
+    filters: Vec<CanFilter>,
+    started: Instant,
+}
+
+impl<T: CanTransport> Sniffer<T> {
+    /// Captures frames passing any of `filters`, or every frame if there are none
+    pub fn try_new(mut transport: T, filters: Vec<CanFilter>) -> Result<Self, T::Error> {
+        transport.set_filter(CanFilter::accept_all())?;
+        Ok(Self {
+            transport,
+            filters,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn filters(&self) -> &[CanFilter] {
+        &self.filters
+    }
+
+    /// Waits up to `timeout` for the next frame passing the filters
+    pub fn capture(&mut self, timeout: Duration) -> Result<Option<CapturedFrame>, T::Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Some(frame) = self.transport.receive(remaining)? else {
+                return Ok(None);
+            };
+            let timestamp = self.started.elapsed();
+
+            if self.filters.is_empty() || self.filters.iter().any(|f| f.matches(frame.id())) {
+                return Ok(Some(CapturedFrame { timestamp, frame }));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransport;
+
+    fn frame(id: CanId, data: &[u8]) -> CanFrame {
+        CanFrame::new(id, data).unwrap()
+    }
+
+    #[test]
+    fn formats_candump_lines() {
+        let captured = CapturedFrame {
+            timestamp: Duration::from_micros(12_345_678),
+            frame: frame(CanId::Standard(0x7E8), &[0x04, 0x41, 0x0C, 0x1A, 0xF8]),
+        };
+        assert_eq!(
+            captured.to_candump("can0"),
+            "(12.345678) can0 7E8#04410C1AF8"
+        );
+
+        let captured = CapturedFrame {
+            timestamp: Duration::from_millis(1500),
+            frame: frame(CanId::Extended(0x18DA_F110), &[]),
+        };
+        assert_eq!(captured.to_candump("twai0"), "(1.500000) twai0 18DAF110#");
+
+        let captured = CapturedFrame {
+            timestamp: Duration::ZERO,
+            frame: frame(CanId::Standard(0x01), &[0x00]),
+        };
+        assert_eq!(captured.to_candump("can0"), "(0.000000) can0 001#00");
+    }
+
+    #[test]
+    fn captures_filtered_frames() {
+        let mut transport = MockTransport::new();
+        let frames = [
+            frame(CanId::Standard(0x123), &[1]),
+            frame(CanId::Standard(0x7E8), &[2]),
+            frame(CanId::Extended(0x18DA_F110), &[3]),
+            frame(CanId::Standard(0x7DF), &[4]),
+        ];
+        for frame in frames {
+            transport.push_rx(frame);
+        }
+
+        let mut sniffer = Sniffer::try_new(
+            &mut transport,
+            vec![
+                CanFilter {
+                    id: 0x7E8,
+                    mask: 0x7F8,
+                    extended: false,
+                },
+                CanFilter {
+                    id: 0x18DA_F100,
+                    mask: 0x1FFF_FF00,
+                    extended: true,
+                },
+            ],
+        )
+        .unwrap();
+
+        let mut captured = vec![];
+        while let Some(frame) = sniffer.capture(Duration::ZERO).unwrap() {
+            captured.push(frame.frame);
+        }
+        assert_eq!(captured, [frames[1], frames[2]]);
+        // Nothing is ever sent
+        assert!(transport.sent().is_empty());
+    }
+}
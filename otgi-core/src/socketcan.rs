@@ -0,0 +1,167 @@
+use crate::can::{CanFilter, CanFrame, CanId, CanTransport};
+use std::{
+    ffi::CString,
+    io,
+    mem::{self, size_of},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    time::Duration,
+};
+
+/// [`CanTransport`] backed by a Linux SocketCAN raw socket (e.g. `can0` or `vcan0`)
+pub struct SocketCanTransport {
+    socket: OwnedFd,
+}
+
+impl SocketCanTransport {
+    pub fn open(interface: &str) -> io::Result<Self> {
+        let name =
+            CString::new(interface).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let fd = unsafe { libc::socket(libc::PF_CAN, libc::SOCK_RAW, libc::CAN_RAW) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Take ownership immediately so the descriptor is closed on any error below
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut addr: libc::sockaddr_can = unsafe { mem::zeroed() };
+        addr.can_family = libc::AF_CAN as libc::sa_family_t;
+        addr.can_ifindex = ifindex as libc::c_int;
+
+        let res = unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                &addr as *const libc::sockaddr_can as *const libc::sockaddr,
+                size_of::<libc::sockaddr_can>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { socket })
+    }
+
+    /// Waits until the socket is ready for `events`, returning false on timeout
+    fn poll(&self, events: libc::c_short, timeout: Duration) -> io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.socket.as_raw_fd(),
+            events,
+            revents: 0,
+        };
+        let timeout_ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+
+        match unsafe { libc::poll(&mut fd, 1, timeout_ms) } {
+            res if res < 0 => Err(io::Error::last_os_error()),
+            0 => Ok(false),
+            _ => Ok(true),
+        }
+    }
+}
+
+impl AsRawFd for SocketCanTransport {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl CanTransport for SocketCanTransport {
+    type Error = io::Error;
+
+    fn transmit(&mut self, frame: &CanFrame, timeout: Duration) -> Result<(), Self::Error> {
+        if !self.poll(libc::POLLOUT, timeout)? {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        let mut raw: libc::can_frame = unsafe { mem::zeroed() };
+        raw.can_id = match frame.id() {
+            CanId::Standard(id) => id as libc::canid_t & libc::CAN_SFF_MASK,
+            CanId::Extended(id) => (id & libc::CAN_EFF_MASK) | libc::CAN_EFF_FLAG,
+        };
+        raw.can_dlc = frame.data().len() as u8;
+        raw.data[..frame.data().len()].copy_from_slice(frame.data());
+
+        let written = unsafe {
+            libc::write(
+                self.socket.as_raw_fd(),
+                &raw as *const libc::can_frame as *const libc::c_void,
+                size_of::<libc::can_frame>(),
+            )
+        };
+        if written < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn receive(&mut self, timeout: Duration) -> Result<Option<CanFrame>, Self::Error> {
+        if !self.poll(libc::POLLIN, timeout)? {
+            return Ok(None);
+        }
+
+        let mut raw: libc::can_frame = unsafe { mem::zeroed() };
+        let read = unsafe {
+            libc::read(
+                self.socket.as_raw_fd(),
+                &mut raw as *mut libc::can_frame as *mut libc::c_void,
+                size_of::<libc::can_frame>(),
+            )
+        };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        } else if read as usize != size_of::<libc::can_frame>() {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let id = if raw.can_id & libc::CAN_EFF_FLAG != 0 {
+            CanId::Extended(raw.can_id & libc::CAN_EFF_MASK)
+        } else {
+            CanId::Standard((raw.can_id & libc::CAN_SFF_MASK) as u16)
+        };
+        let len = (raw.can_dlc as usize).min(raw.data.len());
+
+        Ok(CanFrame::new(id, &raw.data[..len]))
+    }
+
+    fn set_filter(&mut self, filter: CanFilter) -> Result<(), Self::Error> {
+        // The kernel compares the EFF flag as part of the identifier, so include it in the mask
+        // unless the filter accepts everything
+        let raw = if filter.mask == 0 {
+            libc::can_filter {
+                can_id: 0,
+                can_mask: 0,
+            }
+        } else {
+            let eff = if filter.extended {
+                libc::CAN_EFF_FLAG
+            } else {
+                0
+            };
+            libc::can_filter {
+                can_id: filter.id | eff,
+                can_mask: filter.mask | libc::CAN_EFF_FLAG | libc::CAN_RTR_FLAG,
+            }
+        };
+
+        let res = unsafe {
+            libc::setsockopt(
+                self.socket.as_raw_fd(),
+                libc::SOL_CAN_RAW,
+                libc::CAN_RAW_FILTER,
+                &raw as *const libc::can_filter as *const libc::c_void,
+                size_of::<libc::can_filter>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
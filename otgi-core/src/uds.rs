@@ -0,0 +1,164 @@
+use crate::{
+    can::{CanId, CanTransport},
+    obd::{ObdDriver, ObdError},
+};
+use std::time::{Duration, Instant};
+
+/// Tester present is sent this often to keep a non-default session open, well within the
+/// ECU's S3 timeout of 5 s
+pub const TESTER_PRESENT_PERIOD: Duration = Duration::from_secs(2);
+
+/// Set in a sub-function to ask the ECU not to send a positive response
+const SUPPRESS_POSITIVE_RESPONSE: u8 = 0x80;
+
+/// ISO 14229 services supported by [`UdsClient`]
+#[repr(u8)]
+#[derive(strum::FromRepr, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Service {
+    DiagnosticSessionControl = 0x10,
+    ReadDataByIdentifier = 0x22,
+    TesterPresent = 0x3E,
+}
+
+impl Service {
+    /// Number of request bytes after the service ID that a positive response repeats
+    pub(crate) fn echoed_len(&self) -> usize {
+        match self {
+            Service::DiagnosticSessionControl | Service::TesterPresent => 1,
+            Service::ReadDataByIdentifier => 2,
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(strum::FromRepr, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Session {
+    #[default]
+    Default = 0x01,
+    Programming = 0x02,
+    Extended = 0x03,
+}
+
+/// Response times the ECU promises for the session it entered
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SessionTiming {
+    /// P2: time to the first response
+    pub response: Duration,
+    /// P2*: time to the final response after a response pending
+    pub extended_response: Duration,
+}
+
+/// UDS requests to a single ECU, sent through an [`ObdDriver`] so they share its bus, retries
+/// and response matching
+#[derive(Debug)]
+pub struct UdsClient {
+    ecu: CanId,
+    session: Session,
+    last_request: Option<Instant>,
+}
+
+impl UdsClient {
+    /// Talks to the ECU that responds on `ecu`, e.g. 0x7E8 for the engine
+    pub fn new(ecu: CanId) -> Self {
+        Self {
+            ecu,
+            session: Session::Default,
+            last_request: None,
+        }
+    }
+
+    pub fn ecu(&self) -> CanId {
+        self.ecu
+    }
+
+    /// The session as of the last [`UdsClient::start_session`]
+    pub fn session(&self) -> Session {
+        self.session
+    }
+
+    /// Reads the raw value of a data identifier
+    pub fn read_data_by_identifier<T: CanTransport>(
+        &mut self,
+        driver: &mut ObdDriver<T>,
+        did: u16,
+    ) -> Result<Vec<u8>, ObdError<T::Error>> {
+        let [high, low] = did.to_be_bytes();
+        let response = self.request(driver, &[Service::ReadDataByIdentifier as u8, high, low])?;
+        if response.len() <= 3 {
+            log::error!("No data for {:#06X}: {:?}", did, response);
+            return Err(ObdError::MalformedResponse);
+        }
+        Ok(response[3..].to_vec())
+    }
+
+    /// Switches the ECU to `session`, returning the timing it reports
+    pub fn start_session<T: CanTransport>(
+        &mut self,
+        driver: &mut ObdDriver<T>,
+        session: Session,
+    ) -> Result<SessionTiming, ObdError<T::Error>> {
+        let response = self.request(
+            driver,
+            &[Service::DiagnosticSessionControl as u8, session as u8],
+        )?;
+        let [_, _, p2_high, p2_low, p2_star_high, p2_star_low, ..] = response[..] else {
+            return Err(ObdError::MalformedResponse);
+        };
+
+        self.session = session;
+        Ok(SessionTiming {
+            response: Duration::from_millis(u16::from_be_bytes([p2_high, p2_low]) as u64),
+            // P2* is sent in units of 10 ms
+            extended_response: Duration::from_millis(
+                u16::from_be_bytes([p2_star_high, p2_star_low]) as u64 * 10,
+            ),
+        })
+    }
+
+    /// Checks that the ECU is still listening
+    pub fn tester_present<T: CanTransport>(
+        &mut self,
+        driver: &mut ObdDriver<T>,
+    ) -> Result<(), ObdError<T::Error>> {
+        self.request(driver, &[Service::TesterPresent as u8, 0x00])?;
+        Ok(())
+    }
+
+    /// Keeps a non-default session from timing out, sending tester present without asking for a
+    /// response when nothing else has been sent for [`TESTER_PRESENT_PERIOD`]. Returns whether
+    /// it was sent.
+    pub fn keep_alive<T: CanTransport>(
+        &mut self,
+        driver: &mut ObdDriver<T>,
+        now: Instant,
+    ) -> Result<bool, ObdError<T::Error>> {
+        if self.session == Session::Default
+            || self
+                .last_request
+                .is_some_and(|last| now.saturating_duration_since(last) < TESTER_PRESENT_PERIOD)
+        {
+            return Ok(false);
+        }
+
+        driver.send_ecu(
+            self.ecu,
+            &[Service::TesterPresent as u8, SUPPRESS_POSITIVE_RESPONSE],
+        )?;
+        self.last_request = Some(now);
+        Ok(true)
+    }
+
+    fn request<T: CanTransport>(
+        &mut self,
+        driver: &mut ObdDriver<T>,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, ObdError<T::Error>> {
+        self.last_request = Some(Instant::now());
+        let response = driver.request_ecu(self.ecu, payload)?;
+        if response.len() < payload.len() {
+            log::error!("Picked up the wrong packet: {:?}", response);
+            return Err(ObdError::MalformedResponse);
+        }
+        Ok(response)
+    }
+}
@@ -0,0 +1,236 @@
+use core::fmt;
+use strum::IntoEnumIterator;
+
+#[repr(u8)]
+#[derive(strum::FromRepr, strum::EnumIter, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Unit {
+    // The discriminants are sent to the phone so they must never be reordered
+    Percent = 0x00,
+    Ratio = 0x01,
+    Count = 0x02,
+    Rpm = 0x03,
+    Degrees = 0x04,
+    Volts = 0x05,
+    Seconds = 0x06,
+    Minutes = 0x07,
+    KilometersPerHour = 0x10,
+    Kilometers = 0x11,
+    GramsPerSecond = 0x12,
+    LitersPerHour = 0x13,
+    Celsius = 0x14,
+    Kilopascals = 0x15,
+    Pascals = 0x16,
+    NewtonMeters = 0x17,
+    Liters = 0x18,
+    MilesPerHour = 0x20,
+    Miles = 0x21,
+    PoundsPerMinute = 0x22,
+    GallonsPerHour = 0x23,
+    Fahrenheit = 0x24,
+    PoundsPerSquareInch = 0x25,
+    InchesOfWater = 0x26,
+    PoundFeet = 0x27,
+    Gallons = 0x28,
+}
+
+impl Unit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Percent => "%",
+            Self::Ratio => "λ",
+            Self::Count => "",
+            Self::Rpm => "rpm",
+            Self::Degrees => "°",
+            Self::Volts => "V",
+            Self::Seconds => "s",
+            Self::Minutes => "min",
+            Self::KilometersPerHour => "km/h",
+            Self::Kilometers => "km",
+            Self::GramsPerSecond => "g/s",
+            Self::LitersPerHour => "L/h",
+            Self::Celsius => "°C",
+            Self::Kilopascals => "kPa",
+            Self::Pascals => "Pa",
+            Self::NewtonMeters => "N·m",
+            Self::Liters => "L",
+            Self::MilesPerHour => "mph",
+            Self::Miles => "mi",
+            Self::PoundsPerMinute => "lb/min",
+            Self::GallonsPerHour => "gal/h",
+            Self::Fahrenheit => "°F",
+            Self::PoundsPerSquareInch => "psi",
+            Self::InchesOfWater => "inH₂O",
+            Self::PoundFeet => "lb·ft",
+            Self::Gallons => "gal",
+        }
+    }
+
+    /// Looks a unit up by its symbol, ignoring ASCII case
+    pub fn from_symbol(symbol: &str) -> Option<Unit> {
+        Unit::iter().find(|unit| unit.symbol().eq_ignore_ascii_case(symbol))
+    }
+
+    /// The metric unit this unit measures the same quantity as, with `metric = factor * self +
+    /// offset`
+    fn metric(&self) -> (Unit, f32, f32) {
+        match *self {
+            Self::MilesPerHour => (Self::KilometersPerHour, 1.609344, 0.0),
+            Self::Miles => (Self::Kilometers, 1.609344, 0.0),
+            Self::PoundsPerMinute => (Self::GramsPerSecond, 453.59237 / 60.0, 0.0),
+            Self::GallonsPerHour => (Self::LitersPerHour, 3.785_411_8, 0.0),
+            Self::Fahrenheit => (Self::Celsius, 5.0 / 9.0, -32.0 * 5.0 / 9.0),
+            Self::PoundsPerSquareInch => (Self::Kilopascals, 6.894757, 0.0),
+            Self::InchesOfWater => (Self::Pascals, 249.08891, 0.0),
+            Self::PoundFeet => (Self::NewtonMeters, 1.3558179, 0.0),
+            Self::Gallons => (Self::Liters, 3.785_411_8, 0.0),
+            unit => (unit, 1.0, 0.0),
+        }
+    }
+
+    /// The customary US unit for the same quantity; units without one map to themselves
+    pub fn imperial(&self) -> Unit {
+        match *self {
+            Self::KilometersPerHour => Self::MilesPerHour,
+            Self::Kilometers => Self::Miles,
+            Self::GramsPerSecond => Self::PoundsPerMinute,
+            Self::LitersPerHour => Self::GallonsPerHour,
+            Self::Celsius => Self::Fahrenheit,
+            Self::Kilopascals => Self::PoundsPerSquareInch,
+            Self::Pascals => Self::InchesOfWater,
+            Self::NewtonMeters => Self::PoundFeet,
+            Self::Liters => Self::Gallons,
+            unit => unit,
+        }
+    }
+}
+
+/// A decoded value along with the unit it is measured in
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quantity {
+    pub value: f32,
+    pub unit: Unit,
+}
+
+impl Quantity {
+    pub fn new(value: f32, unit: Unit) -> Self {
+        Self { value, unit }
+    }
+
+    /// Converts to `unit`, or `None` if it measures a different quantity
+    pub fn to(&self, unit: Unit) -> Option<f32> {
+        let (from_metric, from_factor, from_offset) = self.unit.metric();
+        let (to_metric, to_factor, to_offset) = unit.metric();
+        if from_metric != to_metric {
+            return None;
+        }
+
+        let metric = self.value * from_factor + from_offset;
+        Some((metric - to_offset) / to_factor)
+    }
+
+    pub fn to_imperial(&self) -> Quantity {
+        let unit = self.unit.imperial();
+        Quantity::new(self.to(unit).unwrap(), unit)
+    }
+
+    /// The value as a little endian f32 followed by the unit, as sent over BLE
+    pub fn to_le_bytes(&self) -> [u8; 5] {
+        let mut bytes = [0; 5];
+        bytes[..4].copy_from_slice(&self.value.to_le_bytes());
+        bytes[4] = self.unit as u8;
+        bytes
+    }
+
+    pub fn from_le_bytes(bytes: [u8; 5]) -> Option<Self> {
+        Some(Self::new(
+            f32::from_le_bytes(bytes[..4].try_into().unwrap()),
+            Unit::from_repr(bytes[4])?,
+        ))
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.unit {
+            Unit::Count => write!(f, "{}", self.value),
+            Unit::Percent | Unit::Degrees => write!(f, "{}{}", self.value, self.unit.symbol()),
+            unit => write!(f, "{} {}", self.value, unit.symbol()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-3 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn converts_to_imperial() {
+        let cases = [
+            (Quantity::new(100.0, Unit::KilometersPerHour), 62.137_12),
+            (Quantity::new(1.609344, Unit::Kilometers), 1.0),
+            (Quantity::new(100.0, Unit::Celsius), 212.0),
+            (Quantity::new(-40.0, Unit::Celsius), -40.0),
+            (Quantity::new(101.325, Unit::Kilopascals), 14.695_95),
+            (Quantity::new(249.08891, Unit::Pascals), 1.0),
+            (Quantity::new(7.559_87, Unit::GramsPerSecond), 1.0),
+            (Quantity::new(3.785_411_8, Unit::LitersPerHour), 1.0),
+            (Quantity::new(100.0, Unit::NewtonMeters), 73.756_21),
+            (Quantity::new(37.854_12, Unit::Liters), 10.0),
+        ];
+
+        for (metric, expected) in cases {
+            let imperial = metric.to_imperial();
+            assert_ne!(imperial.unit, metric.unit);
+            assert!(
+                close(imperial.value, expected),
+                "{} -> {}",
+                metric,
+                imperial
+            );
+            assert!(close(imperial.to(metric.unit).unwrap(), metric.value));
+        }
+    }
+
+    #[test]
+    fn keeps_dimensionless_units() {
+        let rpm = Quantity::new(2500.0, Unit::Rpm);
+        assert_eq!(rpm.to_imperial(), rpm);
+        assert_eq!(rpm.to(Unit::Rpm), Some(2500.0));
+    }
+
+    #[test]
+    fn looks_up_symbols() {
+        assert_eq!(Unit::from_symbol("°C"), Some(Unit::Celsius));
+        assert_eq!(Unit::from_symbol("KPA"), Some(Unit::Kilopascals));
+        assert_eq!(Unit::from_symbol(""), Some(Unit::Count));
+        assert_eq!(Unit::from_symbol("furlongs"), None);
+    }
+
+    #[test]
+    fn refuses_mismatched_quantities() {
+        let speed = Quantity::new(50.0, Unit::KilometersPerHour);
+        assert_eq!(speed.to(Unit::GramsPerSecond), None);
+        assert_eq!(speed.to(Unit::Miles), None);
+        assert_eq!(Quantity::new(10.0, Unit::Percent).to(Unit::Ratio), None);
+    }
+
+    #[test]
+    fn byte_encoding() {
+        let maf = Quantity::new(3.5, Unit::GramsPerSecond);
+        let bytes = maf.to_le_bytes();
+        assert_eq!(bytes, [0x00, 0x00, 0x60, 0x40, 0x12]);
+        assert_eq!(Quantity::from_le_bytes(bytes), Some(maf));
+        assert_eq!(Quantity::from_le_bytes([0, 0, 0, 0, 0xFF]), None);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Quantity::new(88.0, Unit::Celsius).to_string(), "88 °C");
+        assert_eq!(Quantity::new(12.5, Unit::Percent).to_string(), "12.5%");
+        assert_eq!(Quantity::new(3.0, Unit::Count).to_string(), "3");
+    }
+}
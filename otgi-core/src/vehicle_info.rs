@@ -0,0 +1,263 @@
+/// Mode 09 InfoTypes
+#[repr(u8)]
+#[derive(strum::FromRepr, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InfoType {
+    Supported = 0x00,
+    Vin = 0x02,
+    CalibrationIds = 0x04,
+    CalibrationVerificationNumbers = 0x06,
+    InUsePerformanceSpark = 0x08,
+    EcuName = 0x0A,
+    InUsePerformanceCompression = 0x0B,
+}
+
+/// How often a monitor completed compared to how often its conditions were met
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorRatio {
+    pub monitor: &'static str,
+    pub completions: u16,
+    pub conditions: u16,
+}
+
+/// In-use performance tracking counters (InfoType 08 or 0B)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InUsePerformance {
+    pub obd_conditions: u16,
+    pub ignition_cycles: u16,
+    pub monitors: Vec<MonitorRatio>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VehicleInfo {
+    Supported(Vec<u8>),
+    Vin(String),
+    CalibrationIds(Vec<String>),
+    CalibrationVerificationNumbers(Vec<u32>),
+    InUsePerformance(InUsePerformance),
+    EcuName(String),
+}
+
+// Order of the completion/condition counter pairs after OBDCOND and IGNCNTR
+const SPARK_MONITORS: &[&str] = &[
+    "Catalyst bank 1",
+    "Catalyst bank 2",
+    "O2 sensor bank 1",
+    "O2 sensor bank 2",
+    "EGR/VVT",
+    "Secondary air",
+    "EVAP",
+    "Secondary O2 sensor bank 1",
+    "Secondary O2 sensor bank 2",
+];
+const COMPRESSION_MONITORS: &[&str] = &[
+    "NMHC catalyst",
+    "NOx catalyst",
+    "NOx adsorber",
+    "PM filter",
+    "Exhaust gas sensor",
+    "EGR/VVT",
+    "Boost pressure",
+    "Fuel system",
+];
+
+/// Text fields are ASCII padded with zeroes, which are dropped
+fn ascii(data: &[u8]) -> Result<String, ()> {
+    if !data.is_ascii() {
+        return Err(());
+    }
+    Ok(data
+        .iter()
+        .filter(|&&b| b != 0)
+        .map(|&b| b as char)
+        .collect())
+}
+
+impl VehicleInfo {
+    /// Decodes the data following the `49 <InfoType>` header
+    pub(crate) fn decode(info: InfoType, data: &[u8]) -> Result<Self, ()> {
+        if info == InfoType::Supported {
+            if data.len() < 4 {
+                return Err(());
+            }
+            let bitmap = u32::from_be_bytes(data[..4].try_into().unwrap());
+            return Ok(Self::Supported(
+                (1..=0x20)
+                    .filter(|i| bitmap & (1 << (0x20 - i)) != 0)
+                    .collect(),
+            ));
+        }
+
+        // Every other InfoType starts with the number of data items
+        let (&count, items) = data.split_first().ok_or(())?;
+        let count = count as usize;
+
+        match info {
+            InfoType::Vin => {
+                if items.len() < 17 {
+                    return Err(());
+                }
+                Ok(Self::Vin(ascii(&items[..17])?))
+            }
+            InfoType::CalibrationIds => {
+                if items.len() < 16 * count {
+                    return Err(());
+                }
+                Ok(Self::CalibrationIds(
+                    items
+                        .chunks_exact(16)
+                        .take(count)
+                        .map(ascii)
+                        .collect::<Result<_, _>>()?,
+                ))
+            }
+            InfoType::CalibrationVerificationNumbers => {
+                if items.len() < 4 * count {
+                    return Err(());
+                }
+                Ok(Self::CalibrationVerificationNumbers(
+                    items
+                        .chunks_exact(4)
+                        .take(count)
+                        .map(|cvn| u32::from_be_bytes(cvn.try_into().unwrap()))
+                        .collect(),
+                ))
+            }
+            InfoType::InUsePerformanceSpark | InfoType::InUsePerformanceCompression => {
+                // `count` is the number of 16 bit counters
+                if count < 2 || items.len() < 2 * count {
+                    return Err(());
+                }
+                let counters: Vec<u16> = items
+                    .chunks_exact(2)
+                    .take(count)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect();
+                let names = match info {
+                    InfoType::InUsePerformanceSpark => SPARK_MONITORS,
+                    _ => COMPRESSION_MONITORS,
+                };
+
+                Ok(Self::InUsePerformance(InUsePerformance {
+                    obd_conditions: counters[0],
+                    ignition_cycles: counters[1],
+                    monitors: counters[2..]
+                        .chunks_exact(2)
+                        .zip(names)
+                        .map(|(pair, &monitor)| MonitorRatio {
+                            monitor,
+                            completions: pair[0],
+                            conditions: pair[1],
+                        })
+                        .collect(),
+                }))
+            }
+            InfoType::EcuName => {
+                if items.len() < 20 {
+                    return Err(());
+                }
+                Ok(Self::EcuName(ascii(&items[..20])?))
+            }
+            InfoType::Supported => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_supported_info_types() {
+        assert_eq!(
+            VehicleInfo::decode(InfoType::Supported, &[0x55, 0x40, 0x00, 0x00]),
+            Ok(VehicleInfo::Supported(vec![0x02, 0x04, 0x06, 0x08, 0x0A]))
+        );
+        assert!(VehicleInfo::decode(InfoType::Supported, &[0x55, 0x40, 0x00]).is_err());
+    }
+
+    #[test]
+    fn decodes_vin() {
+        let mut data = vec![0x01];
+        data.extend(b"1HGCM82633A004352");
+        assert_eq!(
+            VehicleInfo::decode(InfoType::Vin, &data),
+            Ok(VehicleInfo::Vin("1HGCM82633A004352".to_string()))
+        );
+        assert!(VehicleInfo::decode(InfoType::Vin, &data[..17]).is_err());
+
+        data[5] = 0xC3;
+        assert!(VehicleInfo::decode(InfoType::Vin, &data).is_err());
+    }
+
+    #[test]
+    fn decodes_calibration() {
+        let mut data = vec![0x02];
+        data.extend(b"JMB*36761500\0\0\0\0");
+        data.extend(b"JMB*47872611\0\0\0\0");
+        assert_eq!(
+            VehicleInfo::decode(InfoType::CalibrationIds, &data),
+            Ok(VehicleInfo::CalibrationIds(vec![
+                "JMB*36761500".to_string(),
+                "JMB*47872611".to_string()
+            ]))
+        );
+        assert!(VehicleInfo::decode(InfoType::CalibrationIds, &data[..30]).is_err());
+
+        assert_eq!(
+            VehicleInfo::decode(
+                InfoType::CalibrationVerificationNumbers,
+                &[0x02, 0x17, 0x91, 0xBC, 0x82, 0x16, 0xE0, 0x62, 0xBE]
+            ),
+            Ok(VehicleInfo::CalibrationVerificationNumbers(vec![
+                0x1791BC82, 0x16E062BE
+            ]))
+        );
+    }
+
+    #[test]
+    fn decodes_ecu_name() {
+        let mut data = vec![0x01];
+        data.extend(b"ECM\0-EngineControl\0\0");
+        assert_eq!(
+            VehicleInfo::decode(InfoType::EcuName, &data),
+            Ok(VehicleInfo::EcuName("ECM-EngineControl".to_string()))
+        );
+    }
+
+    #[test]
+    fn decodes_in_use_performance() {
+        // OBDCOND, IGNCNTR and the first two catalyst counters
+        let data = [
+            0x06, 0x00, 0x64, 0x01, 0x2C, 0x00, 0x32, 0x00, 0x5A, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let Ok(VehicleInfo::InUsePerformance(ipt)) =
+            VehicleInfo::decode(InfoType::InUsePerformanceSpark, &data)
+        else {
+            panic!("failed to decode");
+        };
+
+        assert_eq!(ipt.obd_conditions, 100);
+        assert_eq!(ipt.ignition_cycles, 300);
+        assert_eq!(
+            ipt.monitors,
+            [
+                MonitorRatio {
+                    monitor: "Catalyst bank 1",
+                    completions: 50,
+                    conditions: 90
+                },
+                MonitorRatio {
+                    monitor: "Catalyst bank 2",
+                    completions: 0,
+                    conditions: 0
+                },
+            ]
+        );
+
+        assert!(VehicleInfo::decode(InfoType::InUsePerformanceCompression, &data[..12]).is_err());
+        assert!(
+            VehicleInfo::decode(InfoType::InUsePerformanceCompression, &[0x01, 0x00, 0x01])
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,774 @@
+use otgi_core::{
+    can::{CanFrame, CanId},
+    custom_pid::CustomPid,
+    isotp::IsoTpError,
+    mock::MockTransport,
+    obd::{
+        Addressing, ClearChallenge, NegativeResponseCode, ObdDriver, ObdDriverConfig, ObdError,
+        ObdMode, ObdQuery, ObdReadableData, Protocol, Unsolicited, PID,
+    },
+    units::{Quantity, Unit},
+};
+
+use std::{cell::RefCell, collections::BTreeSet, rc::Rc, time::Instant};
+
+fn frame(id: u16, data: &[u8]) -> CanFrame {
+    CanFrame::new(CanId::Standard(id), data).unwrap()
+}
+
+#[test]
+fn single_frame_query() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7DF, &[0x02, 0x01, 0x0C, 0, 0, 0, 0, 0]),
+        [frame(0x7E8, &[0x04, 0x41, 0x0C, 0x1A, 0xF8, 0, 0, 0])],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let rpm = driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::EngineSpeed)));
+
+    assert!(matches!(rpm, Ok(ObdReadableData::Value(q)) if q == Quantity::new(1726.0, Unit::Rpm)));
+    assert!(transport.is_done());
+}
+
+#[test]
+fn multi_frame_dtc_response() {
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7DF, &[0x01, 0x03, 0, 0, 0, 0, 0, 0]),
+            [frame(
+                0x7E8,
+                &[0x10, 0x0A, 0x43, 0x04, 0x01, 0x43, 0x02, 0x00],
+            )],
+        )
+        .expect(
+            frame(0x7E0, &[0x30, 0x00, 0x00, 0, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x21, 0xC1, 0x23, 0x13, 0x01, 0, 0, 0])],
+        );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let codes = driver.query(&ObdQuery::new(ObdMode::QueryDTC, None));
+
+    match codes {
+        Ok(ObdReadableData::DTC(data)) => {
+            let codes: Vec<_> = data.iter().map(|code| code.to_string()).collect();
+            assert_eq!(codes, ["P0143", "P0200", "U0123", "P1301"]);
+        }
+        other => panic!("unexpected response {:?}", other),
+    }
+    assert!(transport.is_done());
+}
+
+#[test]
+fn out_of_order_consecutive_frame() {
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7DF, &[0x01, 0x03, 0, 0, 0, 0, 0, 0]),
+            [frame(
+                0x7E8,
+                &[0x10, 0x0A, 0x43, 0x04, 0x01, 0x43, 0x02, 0x00],
+            )],
+        )
+        .expect(
+            frame(0x7E0, &[0x30, 0x00, 0x00, 0, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x22, 0xC1, 0x23, 0x13, 0x01, 0, 0, 0])],
+        );
+
+    // A broken transfer would otherwise be requested again
+    let config = ObdDriverConfig {
+        retries: 0,
+        ..Default::default()
+    };
+    let mut driver = ObdDriver::try_new(&mut transport, &config).unwrap();
+
+    assert!(matches!(
+        driver.query(&ObdQuery::new(ObdMode::QueryDTC, None)),
+        Err(ObdError::IsoTp(IsoTpError::SequenceMismatch {
+            expected: 1,
+            received: 2
+        }))
+    ));
+    assert_eq!(driver.stats().requests, 1);
+    assert_eq!(driver.stats().malformed_responses, 1);
+}
+
+#[test]
+fn truncated_response_is_malformed() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7DF, &[0x02, 0x01, 0x0C, 0, 0, 0, 0, 0]),
+        [frame(0x7E8, &[0x03, 0x41, 0x0C, 0x1A, 0, 0, 0, 0])],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+
+    assert!(matches!(
+        driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::EngineSpeed))),
+        Err(ObdError::MalformedResponse)
+    ));
+    assert_eq!(driver.stats().malformed_responses, 1);
+}
+
+#[test]
+fn wrong_pid_goes_to_listener() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7DF, &[0x02, 0x01, 0x0C, 0, 0, 0, 0, 0]),
+        [
+            frame(0x7E8, &[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0]),
+            frame(0x7E8, &[0x04, 0x41, 0x0C, 0x1A, 0xF8, 0, 0, 0]),
+        ],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let heard = Rc::new(RefCell::new(vec![]));
+    let sink = heard.clone();
+    driver.set_listener(move |traffic| sink.borrow_mut().push(traffic.clone()));
+
+    let rpm = driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::EngineSpeed)));
+
+    assert!(matches!(rpm, Ok(ObdReadableData::Value(q)) if q.value == 1726.0));
+    assert_eq!(
+        *heard.borrow(),
+        [Unsolicited::Response(
+            CanId::Standard(0x7E8),
+            vec![0x41, 0x0D, 0x32]
+        )]
+    );
+    assert_eq!(driver.stats().unsolicited, 1);
+}
+
+#[test]
+fn drains_late_responses_before_sending() {
+    let mut transport = MockTransport::new();
+    // Answer to an earlier request that timed out
+    let late = frame(0x7E8, &[0x03, 0x41, 0x0C, 0x0B, 0xB8, 0, 0, 0]);
+    transport.push_rx(late).expect(
+        frame(0x7DF, &[0x02, 0x01, 0x0C, 0, 0, 0, 0, 0]),
+        [frame(0x7E8, &[0x04, 0x41, 0x0C, 0x1A, 0xF8, 0, 0, 0])],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let heard = Rc::new(RefCell::new(vec![]));
+    let sink = heard.clone();
+    driver.set_listener(move |traffic| sink.borrow_mut().push(traffic.clone()));
+
+    let rpm = driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::EngineSpeed)));
+
+    assert!(matches!(rpm, Ok(ObdReadableData::Value(q)) if q.value == 1726.0));
+    assert_eq!(*heard.borrow(), [Unsolicited::Frame(late)]);
+    assert!(transport.is_done());
+}
+
+#[test]
+fn no_response_times_out() {
+    let mut transport = MockTransport::new();
+    transport.expect(frame(0x7DF, &[0x02, 0x01, 0x0D, 0, 0, 0, 0, 0]), []);
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+
+    assert!(matches!(
+        driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::VehicleSpeed))),
+        Err(ObdError::IsoTp(IsoTpError::Timeout))
+    ));
+}
+
+#[test]
+fn supported_pids_per_ecu() {
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7DF, &[0x02, 0x01, 0x00, 0, 0, 0, 0, 0]),
+            [
+                frame(0x7E8, &[0x06, 0x41, 0x00, 0x00, 0x18, 0x00, 0x01, 0]),
+                frame(0x7E9, &[0x06, 0x41, 0x00, 0x00, 0x08, 0x00, 0x00, 0]),
+            ],
+        )
+        .expect(
+            frame(0x7DF, &[0x02, 0x01, 0x20, 0, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x06, 0x41, 0x20, 0x00, 0x02, 0x00, 0x00, 0])],
+        );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    driver.discover_supported_pids().unwrap();
+
+    let by_ecu = driver.supported_pids_by_ecu();
+    assert_eq!(
+        by_ecu[&CanId::Standard(0x7E8)],
+        BTreeSet::from([0x0C, 0x0D, 0x20, 0x2F])
+    );
+    assert_eq!(by_ecu[&CanId::Standard(0x7E9)], BTreeSet::from([0x0D]));
+    assert!(driver.is_supported(PID::FuelTankLevelInput));
+    assert!(!driver.is_supported(PID::MassAirFlow));
+    assert!(transport.is_done());
+}
+
+fn confirmed() -> otgi_core::obd::ClearConfirmation {
+    let now = Instant::now();
+    let mut challenge = ClearChallenge::default();
+    let nonce = challenge.issue(42, now);
+    challenge.confirm(nonce, now).unwrap()
+}
+
+#[test]
+fn clears_each_ecu_physically() {
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7DF, &[0x02, 0x01, 0x00, 0, 0, 0, 0, 0]),
+            [
+                frame(0x7E8, &[0x06, 0x41, 0x00, 0x00, 0x18, 0x00, 0x00, 0]),
+                frame(0x7E9, &[0x06, 0x41, 0x00, 0x00, 0x08, 0x00, 0x00, 0]),
+            ],
+        )
+        .expect(
+            frame(0x7E0, &[0x01, 0x04, 0, 0, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x01, 0x44, 0, 0, 0, 0, 0, 0])],
+        )
+        .expect(
+            frame(0x7E1, &[0x01, 0x04, 0, 0, 0, 0, 0, 0]),
+            [frame(0x7E9, &[0x01, 0x44, 0, 0, 0, 0, 0, 0])],
+        );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    assert_eq!(
+        driver.clear_dtcs(confirmed()).unwrap(),
+        [CanId::Standard(0x7E8), CanId::Standard(0x7E9)]
+    );
+    assert!(transport.is_done());
+}
+
+#[test]
+fn clear_without_ecus() {
+    let mut transport = MockTransport::new();
+    transport.expect(frame(0x7DF, &[0x02, 0x01, 0x00, 0, 0, 0, 0, 0]), []);
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    assert!(matches!(
+        driver.clear_dtcs(confirmed()),
+        Err(ObdError::IsoTp(IsoTpError::Timeout))
+    ));
+    assert!(transport.is_done());
+}
+
+#[test]
+fn clear_rejected_by_ecu() {
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7DF, &[0x02, 0x01, 0x00, 0, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x06, 0x41, 0x00, 0x00, 0x18, 0x00, 0x00, 0])],
+        )
+        .expect(
+            frame(0x7E0, &[0x01, 0x04, 0, 0, 0, 0, 0, 0]),
+            // Conditions not correct, e.g. the engine is running
+            [frame(0x7E8, &[0x03, 0x7F, 0x04, 0x22, 0, 0, 0, 0])],
+        );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    assert!(matches!(
+        driver.clear_dtcs(confirmed()),
+        Err(ObdError::NegativeResponse {
+            service: 0x04,
+            code: 0x22
+        })
+    ));
+}
+
+#[test]
+fn multiple_pids_without_the_first() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7DF, &[0x03, 0x01, 0x10, 0x0D, 0, 0, 0, 0]),
+        // The ECU doesn't support MAF, so the reply starts with the speed
+        [frame(0x7E8, &[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0])],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let query = ObdQuery::multiple(&[PID::MassAirFlow, PID::VehicleSpeed]).unwrap();
+    let Ok(ObdReadableData::Values(values)) = driver.query(&query) else {
+        panic!("expected values");
+    };
+
+    assert_eq!(values.len(), 1);
+    assert!(matches!(
+        values[&PID::VehicleSpeed],
+        ObdReadableData::Value(q) if q == Quantity::new(50.0, Unit::KilometersPerHour)
+    ));
+    assert!(transport.is_done());
+}
+
+#[test]
+fn merges_multiple_pids_from_every_ecu() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7DF, &[0x03, 0x01, 0x0C, 0x0D, 0, 0, 0, 0]),
+        [
+            frame(0x7E8, &[0x04, 0x41, 0x0C, 0x1A, 0xF8, 0, 0, 0]),
+            // The transmission only knows the speed
+            frame(0x7E9, &[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0]),
+        ],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let query = ObdQuery::multiple(&[PID::EngineSpeed, PID::VehicleSpeed]).unwrap();
+    let Ok(ObdReadableData::Values(values)) = driver.query(&query) else {
+        panic!("expected values");
+    };
+
+    assert_eq!(values.len(), 2);
+    assert!(matches!(
+        values[&PID::EngineSpeed],
+        ObdReadableData::Value(q) if q == Quantity::new(1726.0, Unit::Rpm)
+    ));
+    assert!(matches!(
+        values[&PID::VehicleSpeed],
+        ObdReadableData::Value(q) if q == Quantity::new(50.0, Unit::KilometersPerHour)
+    ));
+    assert!(transport.is_done());
+}
+
+#[test]
+fn negative_response() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7DF, &[0x02, 0x01, 0x0C, 0, 0, 0, 0, 0]),
+        [frame(0x7E8, &[0x03, 0x7F, 0x01, 0x12, 0, 0, 0, 0])],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let rpm = driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::EngineSpeed)));
+
+    assert!(matches!(
+        rpm,
+        Err(ObdError::NegativeResponse { service: 0x01, code })
+            if NegativeResponseCode::from_repr(code) == Some(NegativeResponseCode::SubFunctionNotSupported)
+    ));
+}
+
+#[test]
+fn waits_for_pending_response() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7DF, &[0x01, 0x03, 0, 0, 0, 0, 0, 0]),
+        [
+            frame(0x7E8, &[0x03, 0x7F, 0x03, 0x78, 0, 0, 0, 0]),
+            frame(0x7E8, &[0x03, 0x7F, 0x03, 0x78, 0, 0, 0, 0]),
+            frame(0x7E8, &[0x04, 0x43, 0x01, 0x01, 0x43, 0, 0, 0]),
+        ],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let codes = driver.query(&ObdQuery::new(ObdMode::QueryDTC, None));
+
+    assert!(
+        matches!(codes, Ok(ObdReadableData::DTC(d)) if d.len() == 1 && d[0].to_string() == "P0143")
+    );
+    assert!(transport.is_done());
+}
+
+#[test]
+fn collects_every_ecu_response() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7DF, &[0x02, 0x01, 0x05, 0, 0, 0, 0, 0]),
+        [
+            frame(0x7E8, &[0x03, 0x41, 0x05, 0x7B, 0, 0, 0, 0]),
+            frame(0x7E9, &[0x03, 0x41, 0x05, 0x50, 0, 0, 0, 0]),
+            frame(0x7EA, &[0x03, 0x7F, 0x01, 0x31, 0, 0, 0, 0]),
+        ],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let responses = driver
+        .query_all(&ObdQuery::new(
+            ObdMode::QueryNow,
+            Some(PID::EngineCoolantTemperature),
+        ))
+        .unwrap();
+
+    assert_eq!(responses.len(), 3);
+    assert!(matches!(
+        responses[&CanId::Standard(0x7E8)],
+        Ok(ObdReadableData::Value(q)) if q == Quantity::new(83.0, Unit::Celsius)
+    ));
+    assert!(matches!(
+        responses[&CanId::Standard(0x7E9)],
+        Ok(ObdReadableData::Value(q)) if q == Quantity::new(40.0, Unit::Celsius)
+    ));
+    assert!(matches!(
+        responses[&CanId::Standard(0x7EA)],
+        Err(ObdError::NegativeResponse {
+            service: 0x01,
+            code: 0x31
+        })
+    ));
+}
+
+#[test]
+fn interleaved_multi_frame_responses() {
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7DF, &[0x01, 0x03, 0, 0, 0, 0, 0, 0]),
+            [
+                frame(0x7E8, &[0x10, 0x0A, 0x43, 0x04, 0x01, 0x43, 0x02, 0x00]),
+                frame(0x7E9, &[0x10, 0x08, 0x43, 0x03, 0x01, 0x00, 0x01, 0x01]),
+            ],
+        )
+        .expect(frame(0x7E0, &[0x30, 0x00, 0x00, 0, 0, 0, 0, 0]), [])
+        .expect(
+            frame(0x7E1, &[0x30, 0x00, 0x00, 0, 0, 0, 0, 0]),
+            [
+                frame(0x7E9, &[0x21, 0x01, 0x02, 0, 0, 0, 0, 0]),
+                frame(0x7E8, &[0x21, 0xC1, 0x23, 0x13, 0x01, 0, 0, 0]),
+            ],
+        );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let responses = driver
+        .query_all(&ObdQuery::new(ObdMode::QueryDTC, None))
+        .unwrap();
+
+    let codes = |ecu| -> Vec<String> {
+        match &responses[&CanId::Standard(ecu)] {
+            Ok(ObdReadableData::DTC(data)) => data.iter().map(|code| code.to_string()).collect(),
+            other => panic!("unexpected response {:?}", other),
+        }
+    };
+    assert_eq!(codes(0x7E8), ["P0143", "P0200", "U0123", "P1301"]);
+    assert_eq!(codes(0x7E9), ["P0100", "P0101", "P0102"]);
+    assert!(transport.is_done());
+}
+
+#[test]
+fn broken_transfer_keeps_other_responses() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7DF, &[0x02, 0x01, 0x05, 0, 0, 0, 0, 0]),
+        [
+            frame(0x7E8, &[0x03, 0x41, 0x05, 0x7B, 0, 0, 0, 0]),
+            // A first frame cut short before its length
+            CanFrame::new(CanId::Standard(0x7E9), &[0x10]).unwrap(),
+        ],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let responses = driver
+        .query_all(&ObdQuery::new(
+            ObdMode::QueryNow,
+            Some(PID::EngineCoolantTemperature),
+        ))
+        .unwrap();
+
+    assert_eq!(responses.len(), 1);
+    assert!(matches!(
+        responses[&CanId::Standard(0x7E8)],
+        Ok(ObdReadableData::Value(q)) if q == Quantity::new(83.0, Unit::Celsius)
+    ));
+}
+
+#[test]
+fn physically_addressed_query() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7E1, &[0x02, 0x01, 0x0D, 0, 0, 0, 0, 0]),
+        [
+            // A late answer from another ECU is skipped
+            frame(0x7E8, &[0x03, 0x41, 0x0D, 0x10, 0, 0, 0, 0]),
+            frame(0x7E9, &[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0]),
+        ],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let speed = driver.query_ecu(
+        CanId::Standard(0x7E9),
+        &ObdQuery::new(ObdMode::QueryNow, Some(PID::VehicleSpeed)),
+    );
+
+    assert!(matches!(
+        speed,
+        Ok(ObdReadableData::Value(q)) if q == Quantity::new(50.0, Unit::KilometersPerHour)
+    ));
+    assert!(transport.is_done());
+}
+
+#[test]
+fn extended_addressing() {
+    let extended = |id: u32, data: &[u8]| CanFrame::new(CanId::Extended(id), data).unwrap();
+
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            extended(0x18DB_33F1, &[0x01, 0x03, 0, 0, 0, 0, 0, 0]),
+            [
+                // 11 bit traffic is filtered out
+                frame(0x7E8, &[0x02, 0x43, 0x00, 0, 0, 0, 0, 0]),
+                extended(
+                    0x18DA_F110,
+                    &[0x10, 0x0A, 0x43, 0x04, 0x01, 0x43, 0x02, 0x00],
+                ),
+            ],
+        )
+        .expect(
+            // Flow control goes to the responding ECU
+            extended(0x18DA_10F1, &[0x30, 0x00, 0x00, 0, 0, 0, 0, 0]),
+            [extended(
+                0x18DA_F110,
+                &[0x21, 0xC1, 0x23, 0x11, 0x01, 0x00, 0x00, 0x00],
+            )],
+        )
+        .expect(
+            extended(0x18DA_10F1, &[0x02, 0x01, 0x0C, 0, 0, 0, 0, 0]),
+            [extended(
+                0x18DA_F110,
+                &[0x04, 0x41, 0x0C, 0x1A, 0xF8, 0, 0, 0],
+            )],
+        );
+
+    let config = ObdDriverConfig {
+        addressing: Addressing::Extended,
+        ..Default::default()
+    };
+    let mut driver = ObdDriver::try_new(&mut transport, &config).unwrap();
+
+    assert!(matches!(
+        driver.query(&ObdQuery::new(ObdMode::QueryDTC, None)),
+        Ok(ObdReadableData::DTC(d)) if d.len() == 4
+    ));
+    assert!(matches!(
+        driver.query_ecu(
+            CanId::Extended(0x18DA_F110),
+            &ObdQuery::new(ObdMode::QueryNow, Some(PID::EngineSpeed))
+        ),
+        Ok(ObdReadableData::Value(q)) if q == Quantity::new(1726.0, Unit::Rpm)
+    ));
+    assert!(transport.is_done());
+}
+
+#[test]
+fn detects_protocol() {
+    let mut tried = vec![];
+    let driver = ObdDriver::detect(
+        |protocol| {
+            tried.push(protocol);
+            let mut transport = MockTransport::new();
+            if protocol == Protocol::Can29Bit250k {
+                transport.expect(
+                    CanFrame::new(
+                        CanId::Extended(0x18DB_33F1),
+                        &[0x02, 0x01, 0x00, 0, 0, 0, 0, 0],
+                    )
+                    .unwrap(),
+                    [CanFrame::new(
+                        CanId::Extended(0x18DA_F110),
+                        &[0x06, 0x41, 0x00, 0x80, 0x00, 0x00, 0x00, 0],
+                    )
+                    .unwrap()],
+                );
+            }
+            // Anything else fails to transmit, as it would without acknowledgement
+            Ok(transport)
+        },
+        Some(Protocol::Can11Bit250k),
+        &Default::default(),
+    )
+    .unwrap();
+
+    assert_eq!(driver.protocol(), Some(Protocol::Can29Bit250k));
+    assert_eq!(
+        tried,
+        [
+            Protocol::Can11Bit250k,
+            Protocol::Can11Bit500k,
+            Protocol::Can29Bit500k,
+            Protocol::Can29Bit250k
+        ]
+    );
+
+    let nothing = ObdDriver::detect(|_| Ok(MockTransport::new()), None, &Default::default());
+    assert!(matches!(nothing, Err(ObdError::ProtocolNotDetected)));
+}
+
+#[test]
+fn recovers_from_bus_off() {
+    let mut transport = MockTransport::new();
+    transport.go_bus_off().expect(
+        frame(0x7DF, &[0x02, 0x01, 0x0D, 0, 0, 0, 0, 0]),
+        [frame(0x7E8, &[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0])],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let speed = driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::VehicleSpeed)));
+
+    assert!(matches!(speed, Ok(ObdReadableData::Value(q)) if q.value == 50.0));
+    let stats = driver.stats();
+    assert_eq!(stats.transport_errors, 1);
+    assert_eq!(stats.bus_off_recoveries, 1);
+    assert_eq!(stats.retries, 1);
+    assert_eq!(stats.responses, 1);
+    assert_eq!(transport.recoveries(), 1);
+    assert!(transport.is_done());
+}
+
+#[test]
+fn retries_busy_ecu() {
+    let request = frame(0x7DF, &[0x02, 0x01, 0x0D, 0, 0, 0, 0, 0]);
+    let busy = frame(0x7E8, &[0x03, 0x7F, 0x01, 0x21, 0, 0, 0, 0]);
+    let mut transport = MockTransport::new();
+    transport
+        .expect(request, [busy])
+        .expect(
+            request,
+            [frame(0x7E8, &[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0])],
+        )
+        .expect(request, [busy])
+        .expect(request, [busy]);
+
+    let config = ObdDriverConfig {
+        retries: 1,
+        ..Default::default()
+    };
+    let mut driver = ObdDriver::try_new(&mut transport, &config).unwrap();
+    let query = ObdQuery::new(ObdMode::QueryNow, Some(PID::VehicleSpeed));
+
+    assert!(driver.query(&query).is_ok());
+    // Out of retries
+    assert!(matches!(
+        driver.query(&query),
+        Err(ObdError::NegativeResponse { code: 0x21, .. })
+    ));
+    let stats = driver.stats();
+    // Repeats count as retries of the same request
+    assert_eq!(stats.requests, 2);
+    assert_eq!(stats.retries, 2);
+    assert_eq!(stats.negative_responses, 3);
+    assert!(transport.is_done());
+}
+
+#[test]
+fn does_not_retry_unanswered_requests() {
+    let mut transport = MockTransport::new();
+    transport.expect(frame(0x7DF, &[0x02, 0x01, 0x0D, 0, 0, 0, 0, 0]), []);
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let speed = driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::VehicleSpeed)));
+
+    assert!(matches!(speed, Err(ObdError::IsoTp(IsoTpError::Timeout))));
+    assert_eq!(driver.stats().timeouts, 1);
+    assert_eq!(driver.stats().retries, 0);
+    assert!(transport.is_done());
+}
+
+#[test]
+fn custom_pids() {
+    let pids = CustomPid::parse_csv(
+        "Trans Temp,TFT,221E1C,((A*256)+B)/16,-40,150,°C,7E1\n\
+         Boost,Boost,010B,A-100,,,kPa\n",
+    )
+    .unwrap();
+
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7E1, &[0x03, 0x22, 0x1E, 0x1C, 0, 0, 0, 0]),
+            [frame(0x7E9, &[0x05, 0x62, 0x1E, 0x1C, 0x05, 0x20, 0, 0])],
+        )
+        .expect(
+            frame(0x7DF, &[0x02, 0x01, 0x0B, 0, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x03, 0x41, 0x0B, 0xAF, 0, 0, 0, 0])],
+        )
+        .expect(
+            frame(0x7DF, &[0x02, 0x01, 0x0B, 0, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x02, 0x41, 0x0B, 0, 0, 0, 0, 0])],
+        )
+        .expect(
+            frame(0x7E1, &[0x03, 0x22, 0x1E, 0x1C, 0, 0, 0, 0]),
+            [frame(0x7E9, &[0x05, 0x62, 0x1E, 0x1C, 0xFF, 0xFF, 0, 0])],
+        );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+
+    assert_eq!(
+        driver.query_custom(&pids[0]),
+        Ok(Quantity::new(82.0, Unit::Celsius))
+    );
+    assert_eq!(
+        driver.query_custom(&pids[1]),
+        Ok(Quantity::new(75.0, Unit::Kilopascals))
+    );
+    // No data byte for A
+    assert_eq!(
+        driver.query_custom(&pids[1]),
+        Err(ObdError::MalformedResponse)
+    );
+    // Above the maximum
+    assert_eq!(
+        driver.query_custom(&pids[0]),
+        Err(ObdError::MalformedResponse)
+    );
+    assert!(transport.is_done());
+}
+
+#[test]
+fn custom_pid_headers_outside_response_range() {
+    let pids = CustomPid::parse_csv(
+        "Body Voltage,BV,22F40D,A/10,,,V,7C0\n\
+         Trans Temp,TFT,221E1C,A-40,,,°C,7E1\n",
+    )
+    .unwrap();
+    let extended = |id: u32, data: &[u8]| CanFrame::new(CanId::Extended(id), data).unwrap();
+
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7C0, &[0x03, 0x22, 0xF4, 0x0D, 0, 0, 0, 0]),
+            [frame(0x7C8, &[0x04, 0x62, 0xF4, 0x0D, 0x8C, 0, 0, 0])],
+        )
+        .expect(
+            frame(0x7DF, &[0x02, 0x01, 0x0D, 0, 0, 0, 0, 0]),
+            [
+                // Back to the usual response IDs
+                frame(0x7C8, &[0x04, 0x62, 0xF4, 0x0D, 0x8C, 0, 0, 0]),
+                frame(0x7E8, &[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0]),
+            ],
+        );
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+
+    assert_eq!(
+        driver.query_custom(&pids[0]),
+        Ok(Quantity::new(14.0, Unit::Volts))
+    );
+    assert!(driver
+        .query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::VehicleSpeed)))
+        .is_ok());
+    assert!(transport.is_done());
+
+    // An 11 bit header on a 29 bit bus
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7E1, &[0x03, 0x22, 0x1E, 0x1C, 0, 0, 0, 0]),
+            [frame(0x7E9, &[0x04, 0x62, 0x1E, 0x1C, 0x82, 0, 0, 0])],
+        )
+        .expect(
+            extended(0x18DB_33F1, &[0x02, 0x01, 0x0D, 0, 0, 0, 0, 0]),
+            [extended(0x18DA_F110, &[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0])],
+        );
+    let config = ObdDriverConfig {
+        addressing: Addressing::Extended,
+        ..Default::default()
+    };
+    let mut driver = ObdDriver::try_new(&mut transport, &config).unwrap();
+
+    assert_eq!(
+        driver.query_custom(&pids[1]),
+        Ok(Quantity::new(90.0, Unit::Celsius))
+    );
+    assert!(driver
+        .query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::VehicleSpeed)))
+        .is_ok());
+    assert!(transport.is_done());
+}
@@ -0,0 +1,475 @@
+use otgi_core::{
+    can::{CanFrame, CanId},
+    dtc::DiagnosticTroubleCode,
+    fuel,
+    isotp::IsoTpError,
+    mock::MockTransport,
+    obd::{ClearChallenge, ObdDriver, ObdError, ObdMode, ObdQuery, ObdReadableData, PID},
+    readiness::{Ignition, Monitor},
+    sim::{self, EcuSimulator, Scenario, SimTransport},
+    vehicle_info::{InfoType, VehicleInfo},
+};
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+use strum::IntoEnumIterator;
+
+const SCENARIO: &str = include_str!("../scenarios/idle_to_cruise.txt");
+
+fn driver() -> ObdDriver<SimTransport> {
+    let scenario: Scenario = SCENARIO.parse().unwrap();
+    let transport = SimTransport::new(EcuSimulator::new(scenario));
+    ObdDriver::try_new(transport, &Default::default()).unwrap()
+}
+
+fn value(data: ObdReadableData) -> f32 {
+    match data {
+        ObdReadableData::Value(q) => q.value,
+        other => panic!("expected a value, got {:?}", other),
+    }
+}
+
+fn dtc(code: &str) -> DiagnosticTroubleCode {
+    code.parse().unwrap()
+}
+
+fn raw_request(ecu: &mut EcuSimulator, id: u16, data: &[u8], now: Duration) -> Vec<Vec<u8>> {
+    ecu.handle(&CanFrame::new(CanId::Standard(id), data).unwrap(), now)
+        .iter()
+        .map(|frame| frame.data().to_vec())
+        .collect()
+}
+
+#[test]
+fn every_pid_matches_scenario() {
+    let mut driver = driver();
+
+    for t in [0, 15, 45, 77, 200] {
+        let now = Duration::from_secs(t);
+        let elapsed = driver.transport().elapsed();
+        driver.transport().advance(now - elapsed);
+
+        let pids: Vec<PID> = driver.transport().ecu().scenario().pids().collect();
+        for pid in pids {
+            let expected = driver.transport().ecu().scenario().value(pid, now).unwrap();
+            let actual = value(
+                driver
+                    .query(&ObdQuery::new(ObdMode::QueryNow, Some(pid)))
+                    .unwrap(),
+            );
+
+            // Quantization of the smallest scaled PIDs is under 1%
+            assert!(
+                (actual - expected).abs() <= 0.5 + expected.abs() * 0.001,
+                "{:?} at {}s: {} != {}",
+                pid,
+                t,
+                actual,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn stored_dtcs_over_multi_frame() {
+    let mut driver = driver();
+    let query = ObdQuery::new(ObdMode::QueryDTC, None);
+
+    assert!(matches!(driver.query(&query), Ok(ObdReadableData::DTC(d)) if d.is_empty()));
+
+    driver.transport().advance(Duration::from_secs(80));
+    assert!(matches!(driver.query(&query), Ok(ObdReadableData::DTC(d)) if d == [dtc("P0301")]));
+
+    let scenario: Scenario = "0 dtc P0301 P0420 C1234 U0100".parse().unwrap();
+    let transport = SimTransport::new(EcuSimulator::new(scenario));
+    let mut driver = ObdDriver::try_new(transport, &Default::default()).unwrap();
+    assert!(matches!(
+        driver.query(&query),
+        Ok(ObdReadableData::DTC(d))
+            if d == [dtc("P0301"), dtc("P0420"), dtc("C1234"), dtc("U0100")]
+    ));
+}
+
+#[test]
+fn supported_pid_bitmaps() {
+    let mut ecu = EcuSimulator::new(SCENARIO.parse().unwrap());
+
+    // 0x01 0x04-0x07 0x0C 0x0D 0x0F-0x11 0x1F and 0x20
+    assert_eq!(
+        raw_request(&mut ecu, 0x7DF, &[0x02, 0x01, 0x00], Duration::ZERO),
+        [[0x06, 0x41, 0x00, 0x9E, 0x1B, 0x80, 0x03, 0x00]]
+    );
+    // 0x2F 0x33 and 0x40
+    assert_eq!(
+        raw_request(&mut ecu, 0x7DF, &[0x02, 0x01, 0x20], Duration::ZERO),
+        [[0x06, 0x41, 0x20, 0x00, 0x02, 0x20, 0x01, 0x00]]
+    );
+}
+
+#[test]
+fn freeze_frame_and_clear() {
+    let mut ecu = EcuSimulator::new(SCENARIO.parse().unwrap());
+    let now = Duration::from_secs(100);
+
+    // Nothing stored yet
+    assert_eq!(
+        raw_request(&mut ecu, 0x7E0, &[0x03, 0x02, 0x02, 0x00], Duration::ZERO),
+        [[0x05, 0x42, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00]]
+    );
+    assert!(raw_request(&mut ecu, 0x7E0, &[0x03, 0x02, 0x0C, 0x00], Duration::ZERO).is_empty());
+
+    // DTC that stored the frame
+    assert_eq!(
+        raw_request(&mut ecu, 0x7E0, &[0x03, 0x02, 0x02, 0x00], now),
+        [[0x05, 0x42, 0x02, 0x00, 0x03, 0x01, 0x00, 0x00]]
+    );
+
+    // Engine speed when the misfire was stored at 75s (2320 rpm)
+    assert_eq!(
+        raw_request(&mut ecu, 0x7E0, &[0x03, 0x02, 0x0C, 0x00], now),
+        [[0x05, 0x42, 0x0C, 0x00, 0x24, 0x40, 0x00, 0x00]]
+    );
+
+    assert_eq!(
+        raw_request(&mut ecu, 0x7DF, &[0x01, 0x04], now),
+        [[0x01, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]]
+    );
+    assert_eq!(
+        raw_request(&mut ecu, 0x7DF, &[0x01, 0x03], now),
+        [[0x02, 0x43, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]]
+    );
+}
+
+#[test]
+fn reads_vehicle_information() {
+    let mut driver = driver();
+    assert_eq!(driver.vin(), None);
+
+    assert_eq!(
+        driver.read_vehicle_info(InfoType::Supported).unwrap(),
+        VehicleInfo::Supported(vec![0x02, 0x04, 0x06, 0x0A])
+    );
+    // 20 bytes, so this goes over multiple frames
+    assert_eq!(driver.read_vin().unwrap(), "1HGCM82633A004352");
+    assert_eq!(driver.vin(), Some("1HGCM82633A004352"));
+    assert_eq!(
+        driver.read_vehicle_info(InfoType::CalibrationIds).unwrap(),
+        VehicleInfo::CalibrationIds(vec!["OTGI-SIM".to_string()])
+    );
+    assert_eq!(
+        driver
+            .read_vehicle_info(InfoType::CalibrationVerificationNumbers)
+            .unwrap(),
+        VehicleInfo::CalibrationVerificationNumbers(vec![sim::CALIBRATION_VERIFICATION_NUMBER])
+    );
+    assert_eq!(
+        driver.read_vehicle_info(InfoType::EcuName).unwrap(),
+        VehicleInfo::EcuName("ECM-EngineControl".to_string())
+    );
+    assert!(matches!(
+        driver.read_vehicle_info(InfoType::InUsePerformanceSpark),
+        Err(ObdError::IsoTp(IsoTpError::Timeout))
+    ));
+}
+
+#[test]
+fn pending_and_permanent_codes() {
+    let mut driver = driver();
+    let stored = ObdQuery::new(ObdMode::QueryDTC, None);
+    let pending = ObdQuery::new(ObdMode::QueryPendingDTC, None);
+    let permanent = ObdQuery::new(ObdMode::QueryPermanentDTC, None);
+
+    driver.transport().advance(Duration::from_secs(65));
+    assert!(matches!(driver.query(&stored), Ok(ObdReadableData::DTC(d)) if d.is_empty()));
+    assert!(
+        matches!(driver.query(&pending), Ok(ObdReadableData::PendingDTC(d)) if d == [dtc("P0301")])
+    );
+
+    driver.transport().advance(Duration::from_secs(15));
+    let now = Instant::now();
+    let mut challenge = ClearChallenge::default();
+    let nonce = challenge.issue(1, now);
+    driver
+        .clear_dtcs(challenge.confirm(nonce, now).unwrap())
+        .unwrap();
+
+    // Only the permanent code survives the clear
+    assert!(matches!(driver.query(&stored), Ok(ObdReadableData::DTC(d)) if d.is_empty()));
+    assert!(matches!(driver.query(&pending), Ok(ObdReadableData::PendingDTC(d)) if d.is_empty()));
+    assert!(matches!(
+        driver.query(&permanent),
+        Ok(ObdReadableData::PermanentDTC(d)) if d == [dtc("P0301")]
+    ));
+}
+
+#[test]
+fn reads_freeze_frame() {
+    let mut driver = driver();
+    let pids = [PID::EngineSpeed, PID::VehicleSpeed, PID::FuelRailPressure];
+    assert_eq!(driver.read_freeze_frame(0, &pids).unwrap(), None);
+
+    driver.transport().advance(Duration::from_secs(100));
+    let frame = driver.read_freeze_frame(0, &pids).unwrap().unwrap();
+    assert_eq!(frame.dtc, dtc("P0301"));
+
+    // Values are those at 75s when the misfire was stored, PIDs missing from the scenario are
+    // skipped
+    let scenario: Scenario = SCENARIO.parse().unwrap();
+    let set = Duration::from_secs(75);
+    assert_eq!(frame.values.len(), 2);
+    for (pid, value) in frame.values {
+        let expected = scenario.value(pid, set).unwrap();
+        assert!((value.value - expected).abs() < 0.5, "{:?}", pid);
+    }
+
+    // Only frame 0 exists
+    assert!(driver.read_freeze_frame(1, &pids).is_err());
+}
+
+#[test]
+fn clears_stored_codes() {
+    let mut driver = driver();
+    driver.transport().advance(Duration::from_secs(80));
+    let query = ObdQuery::new(ObdMode::QueryDTC, None);
+    assert!(matches!(driver.query(&query), Ok(ObdReadableData::DTC(d)) if d.len() == 1));
+
+    let now = Instant::now();
+    let mut challenge = ClearChallenge::default();
+    let nonce = challenge.issue(7, now);
+    let confirmation = challenge.confirm(nonce, now).unwrap();
+
+    assert_eq!(
+        driver.clear_dtcs(confirmation).unwrap(),
+        [CanId::Standard(0x7E8)]
+    );
+    assert!(matches!(driver.query(&query), Ok(ObdReadableData::DTC(d)) if d.is_empty()));
+}
+
+#[test]
+fn multiple_pids_in_one_request() {
+    let mut driver = driver();
+    driver.transport().advance(Duration::from_secs(60));
+    let scenario: Scenario = SCENARIO.parse().unwrap();
+    let now = driver.transport().elapsed();
+
+    let pids = [
+        PID::MassAirFlow,
+        PID::ShortTermFuelTrimBankOne,
+        PID::LongTermFuelTrimBankOne,
+        PID::VehicleSpeed,
+        PID::EngineSpeed,
+        PID::EngineOilTemperature,
+    ];
+    let Ok(ObdReadableData::Values(values)) = driver.query(&ObdQuery::multiple(&pids).unwrap())
+    else {
+        panic!("expected values");
+    };
+
+    // Oil temperature isn't in the scenario
+    assert_eq!(values.len(), 5);
+    for pid in &pids[..5] {
+        let expected = scenario.value(*pid, now).unwrap();
+        let value = match &values[pid] {
+            ObdReadableData::Value(q) => q.value,
+            other => panic!("expected a value, got {:?}", other),
+        };
+        assert!((value - expected).abs() < 1.0, "{:?}", pid);
+    }
+}
+
+#[test]
+fn single_pid_as_multiple() {
+    let mut driver = driver();
+    let query = ObdQuery::multiple(&[PID::EngineSpeed]).unwrap();
+
+    let Ok(ObdReadableData::Values(mut values)) = driver.query(&query) else {
+        panic!("expected values");
+    };
+    assert_eq!(values.len(), 1);
+    let expected = driver
+        .transport()
+        .ecu()
+        .scenario()
+        .value(PID::EngineSpeed, Duration::ZERO)
+        .unwrap();
+    assert_eq!(value(values.remove(&PID::EngineSpeed).unwrap()), expected);
+}
+
+#[test]
+fn reserved_bytes_in_multiple_pids() {
+    // Maximum MAF is followed by three reserved bytes that mustn't be read as the next PID
+    let scenario: Scenario = "0 MaximumMassAirFlow 250\n0 VehicleSpeed 30\n"
+        .parse()
+        .unwrap();
+    let transport = SimTransport::new(EcuSimulator::new(scenario));
+    let mut driver = ObdDriver::try_new(transport, &Default::default()).unwrap();
+
+    let pids = [PID::MaximumMassAirFlow, PID::VehicleSpeed];
+    let Ok(ObdReadableData::Values(mut values)) = driver.query(&ObdQuery::multiple(&pids).unwrap())
+    else {
+        panic!("expected values");
+    };
+
+    assert_eq!(values.len(), 2);
+    assert_eq!(
+        value(values.remove(&PID::MaximumMassAirFlow).unwrap()),
+        250.0
+    );
+    assert_eq!(value(values.remove(&PID::VehicleSpeed).unwrap()), 30.0);
+}
+
+#[test]
+fn readiness_after_clear() {
+    let mut driver = driver();
+    let query = ObdQuery::new(ObdMode::QueryNow, Some(PID::MonitorStatus));
+    let status = |driver: &mut ObdDriver<SimTransport>| match driver.query(&query) {
+        Ok(ObdReadableData::MonitorStatus(status)) => status,
+        other => panic!("expected a monitor status, got {:?}", other),
+    };
+
+    let before = status(&mut driver);
+    assert!(!before.mil);
+    assert!(before.ready());
+
+    driver.transport().advance(Duration::from_secs(80));
+    let stored = status(&mut driver);
+    assert!(stored.mil);
+    assert_eq!(stored.dtc_count, 1);
+    assert_eq!(stored.ignition, Ignition::Spark);
+
+    let now = Instant::now();
+    let mut challenge = ClearChallenge::default();
+    let nonce = challenge.issue(7, now);
+    driver
+        .clear_dtcs(challenge.confirm(nonce, now).unwrap())
+        .unwrap();
+
+    // The non-continuous monitors have to run again
+    let cleared = status(&mut driver);
+    assert!(!cleared.mil);
+    assert_eq!(
+        cleared.incomplete().collect::<Vec<_>>(),
+        [
+            Monitor::Catalyst,
+            Monitor::EvaporativeSystem,
+            Monitor::OxygenSensor,
+            Monitor::OxygenSensorHeater,
+            Monitor::EgrVvtSystem
+        ]
+    );
+
+    driver.transport().advance(sim::MONITOR_DRIVE_CYCLE);
+    assert!(status(&mut driver).ready());
+}
+
+#[test]
+fn fuel_integration() {
+    let mut driver = driver();
+    let scenario: Scenario = SCENARIO.parse().unwrap();
+    let step = Duration::from_millis(500);
+
+    let mut liters_used = 0.0;
+    let mut expected = 0.0;
+    while driver.transport().elapsed() < Duration::from_secs(120) {
+        let now = driver.transport().elapsed();
+        let mut read = |pid| {
+            value(
+                driver
+                    .query(&ObdQuery::new(ObdMode::QueryNow, Some(pid)))
+                    .unwrap(),
+            )
+        };
+        let maf = read(PID::MassAirFlow);
+        let stft = read(PID::ShortTermFuelTrimBankOne);
+        let ltft = read(PID::LongTermFuelTrimBankOne);
+        liters_used += fuel::fuel_rate(maf, stft, ltft) / 3600.0 * step.as_secs_f32();
+
+        let exact = |pid| scenario.value(pid, now).unwrap();
+        expected += fuel::fuel_rate(
+            exact(PID::MassAirFlow),
+            exact(PID::ShortTermFuelTrimBankOne),
+            exact(PID::LongTermFuelTrimBankOne),
+        ) / 3600.0
+            * step.as_secs_f32();
+
+        driver.transport().advance(step);
+    }
+
+    assert!(liters_used > 0.1);
+    assert!((liters_used - expected).abs() / expected < 0.01);
+}
+
+#[test]
+fn discovers_supported_pids() {
+    let mut driver = driver();
+
+    // Nothing is ruled out before discovery
+    assert!(driver.is_supported(PID::EngineFuelRate));
+
+    driver.discover_supported_pids().unwrap();
+
+    let expected: HashSet<PID> = driver
+        .transport()
+        .ecu()
+        .scenario()
+        .pids()
+        .chain([
+            PID::MonitorStatus,
+            PID::SecondCap,
+            PID::ThirdCap,
+            PID::FourthCap,
+            PID::FifthCap,
+            PID::SixthCap,
+        ])
+        .collect();
+    assert_eq!(driver.supported_pids(), expected);
+
+    let scenario: Scenario = "0 EngineSpeed 800\n0 VehicleSpeed 0".parse().unwrap();
+    let transport = SimTransport::new(EcuSimulator::new(scenario));
+    let mut driver = ObdDriver::try_new(transport, &Default::default()).unwrap();
+    driver.discover_supported_pids().unwrap();
+
+    assert_eq!(
+        driver.supported_pids(),
+        HashSet::from([PID::MonitorStatus, PID::EngineSpeed, PID::VehicleSpeed])
+    );
+    assert!(!driver.is_supported(PID::MassAirFlow));
+}
+
+#[test]
+fn every_pid_round_trips() {
+    for pid in PID::iter().filter(PID::has_value) {
+        // Decode arbitrary bytes, then have the simulator encode that value back
+        let mut transport = MockTransport::new();
+        transport.expect(
+            CanFrame::new(
+                CanId::Standard(0x7DF),
+                &[0x02, 0x01, pid as u8, 0, 0, 0, 0, 0],
+            )
+            .unwrap(),
+            [CanFrame::new(
+                CanId::Standard(0x7E8),
+                &[0x06, 0x41, pid as u8, 0x12, 0x34, 0x56, 0x78, 0],
+            )
+            .unwrap()],
+        );
+        let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+        let query = ObdQuery::new(ObdMode::QueryNow, Some(pid));
+        let expected = value(driver.query(&query).unwrap());
+
+        let scenario: Scenario = format!("0 {:?} {}", pid, expected).parse().unwrap();
+        let transport = SimTransport::new(EcuSimulator::new(scenario));
+        let mut driver = ObdDriver::try_new(transport, &Default::default()).unwrap();
+        let actual = value(driver.query(&query).unwrap());
+
+        assert!(
+            (actual - expected).abs() <= 1e-6 * expected.abs(),
+            "{:?}: {} != {}",
+            pid,
+            actual,
+            expected
+        );
+    }
+}
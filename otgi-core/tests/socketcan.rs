@@ -0,0 +1,82 @@
+//! Runs against a virtual bus, set one up with:
+//! `ip link add dev vcan0 type vcan && ip link set up vcan0`
+#![cfg(all(feature = "socketcan", target_os = "linux"))]
+
+use otgi_core::{
+    can::{CanFrame, CanId, CanTransport},
+    obd::{ObdDriver, ObdMode, ObdQuery, ObdReadableData, PID},
+    sim::{EcuSimulator, Scenario},
+    socketcan::SocketCanTransport,
+    units::{Quantity, Unit},
+};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+const INTERFACE: &str = "vcan0";
+
+fn open() -> Option<SocketCanTransport> {
+    match SocketCanTransport::open(INTERFACE) {
+        Ok(transport) => Some(transport),
+        Err(e) => {
+            eprintln!("Skipping, {INTERFACE} unavailable: {e}");
+            None
+        }
+    }
+}
+
+#[test]
+fn query_over_virtual_bus() {
+    let (Some(mut ecu), Some(tester)) = (open(), open()) else {
+        return;
+    };
+
+    let responder = thread::spawn(move || {
+        let request = ecu.receive(Duration::from_secs(2)).unwrap().unwrap();
+        assert_eq!(request.id(), CanId::Standard(0x7DF));
+        assert_eq!(&request.data()[..3], &[0x02, 0x01, 0x0D]);
+
+        ecu.transmit(
+            &CanFrame::new(
+                CanId::Standard(0x7E8),
+                &[0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0],
+            )
+            .unwrap(),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+    });
+
+    let mut driver = ObdDriver::try_new(tester, &Default::default()).unwrap();
+    let speed = driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::VehicleSpeed)));
+    responder.join().unwrap();
+
+    assert!(
+        matches!(speed, Ok(ObdReadableData::Value(q)) if q == Quantity::new(50.0, Unit::KilometersPerHour))
+    );
+}
+
+#[test]
+fn simulated_ecu_over_virtual_bus() {
+    let (Some(mut ecu_bus), Some(tester)) = (open(), open()) else {
+        return;
+    };
+
+    let scenario: Scenario = "0 EngineSpeed 1726\n0 dtc P0301 P0420 C1234 U0100"
+        .parse()
+        .unwrap();
+    let ecu = thread::spawn(move || {
+        EcuSimulator::new(scenario)
+            .run(&mut ecu_bus, Some(Instant::now() + Duration::from_secs(1)))
+            .unwrap();
+    });
+
+    let mut driver = ObdDriver::try_new(tester, &Default::default()).unwrap();
+    let rpm = driver.query(&ObdQuery::new(ObdMode::QueryNow, Some(PID::EngineSpeed)));
+    let codes = driver.query(&ObdQuery::new(ObdMode::QueryDTC, None));
+    ecu.join().unwrap();
+
+    assert!(matches!(rpm, Ok(ObdReadableData::Value(q)) if q == Quantity::new(1726.0, Unit::Rpm)));
+    assert!(matches!(codes, Ok(ObdReadableData::DTC(d)) if d.len() == 4));
+}
@@ -0,0 +1,134 @@
+use otgi_core::{
+    can::{CanFrame, CanId},
+    mock::MockTransport,
+    obd::{ObdDriver, ObdError},
+    uds::{Session, SessionTiming, UdsClient, TESTER_PRESENT_PERIOD},
+};
+
+use std::time::{Duration, Instant};
+
+fn frame(id: u16, data: &[u8]) -> CanFrame {
+    CanFrame::new(CanId::Standard(id), data).unwrap()
+}
+
+#[test]
+fn reads_data_by_identifier() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7E0, &[0x03, 0x22, 0x11, 0xBD, 0, 0, 0, 0]),
+        [frame(0x7E8, &[0x05, 0x62, 0x11, 0xBD, 0x00, 0x5A, 0, 0])],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let mut uds = UdsClient::new(CanId::Standard(0x7E8));
+
+    assert_eq!(
+        uds.read_data_by_identifier(&mut driver, 0x11BD),
+        Ok(vec![0x00, 0x5A])
+    );
+    assert!(transport.is_done());
+}
+
+#[test]
+fn identifier_without_data() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7E0, &[0x03, 0x22, 0x11, 0xBD, 0, 0, 0, 0]),
+        [frame(0x7E8, &[0x03, 0x62, 0x11, 0xBD, 0, 0, 0, 0])],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let mut uds = UdsClient::new(CanId::Standard(0x7E8));
+
+    assert_eq!(
+        uds.read_data_by_identifier(&mut driver, 0x11BD),
+        Err(ObdError::MalformedResponse)
+    );
+}
+
+#[test]
+fn reads_multi_frame_identifier() {
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7E0, &[0x03, 0x22, 0xF1, 0x90, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x10, 0x0A, 0x62, 0xF1, 0x90, 1, 2, 3])],
+        )
+        .expect(
+            frame(0x7E0, &[0x30, 0x00, 0x00, 0, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x21, 4, 5, 6, 7, 0, 0, 0])],
+        );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let mut uds = UdsClient::new(CanId::Standard(0x7E8));
+
+    assert_eq!(
+        uds.read_data_by_identifier(&mut driver, 0xF190),
+        Ok(vec![1, 2, 3, 4, 5, 6, 7])
+    );
+    assert!(transport.is_done());
+}
+
+#[test]
+fn unknown_identifier() {
+    let mut transport = MockTransport::new();
+    transport.expect(
+        frame(0x7E0, &[0x03, 0x22, 0x12, 0x34, 0, 0, 0, 0]),
+        [frame(0x7E8, &[0x03, 0x7F, 0x22, 0x31, 0, 0, 0, 0])],
+    );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let mut uds = UdsClient::new(CanId::Standard(0x7E8));
+
+    assert_eq!(
+        uds.read_data_by_identifier(&mut driver, 0x1234),
+        Err(ObdError::NegativeResponse {
+            service: 0x22,
+            code: 0x31
+        })
+    );
+}
+
+#[test]
+fn extended_session_with_keep_alive() {
+    let mut transport = MockTransport::new();
+    transport
+        .expect(
+            frame(0x7E0, &[0x02, 0x10, 0x03, 0, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x06, 0x50, 0x03, 0x00, 0x32, 0x01, 0xF4, 0])],
+        )
+        .expect(frame(0x7E0, &[0x02, 0x3E, 0x80, 0, 0, 0, 0, 0]), [])
+        .expect(
+            frame(0x7E0, &[0x02, 0x3E, 0x00, 0, 0, 0, 0, 0]),
+            [frame(0x7E8, &[0x02, 0x7E, 0x00, 0, 0, 0, 0, 0])],
+        );
+
+    let mut driver = ObdDriver::try_new(&mut transport, &Default::default()).unwrap();
+    let mut uds = UdsClient::new(CanId::Standard(0x7E8));
+
+    // Nothing to keep alive in the default session
+    assert_eq!(uds.keep_alive(&mut driver, Instant::now()), Ok(false));
+
+    assert_eq!(
+        uds.start_session(&mut driver, Session::Extended),
+        Ok(SessionTiming {
+            response: Duration::from_millis(50),
+            extended_response: Duration::from_secs(5),
+        })
+    );
+    assert_eq!(uds.session(), Session::Extended);
+
+    let now = Instant::now();
+    assert_eq!(uds.keep_alive(&mut driver, now), Ok(false));
+    assert_eq!(
+        uds.keep_alive(&mut driver, now + TESTER_PRESENT_PERIOD),
+        Ok(true)
+    );
+    assert_eq!(
+        uds.keep_alive(&mut driver, now + TESTER_PRESENT_PERIOD),
+        Ok(false)
+    );
+
+    assert_eq!(uds.tester_present(&mut driver), Ok(()));
+    assert!(transport.is_done());
+}
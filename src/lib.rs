@@ -1,4 +1,8 @@
 #![allow(clippy::uninlined_format_args)]
 
-pub mod obd;
+pub use otgi_core::{
+    can, custom_pid, dtc, formula, fuel, isotp, obd, readiness, scheduler, sniffer, uds, units,
+    vehicle_info,
+};
+pub mod twai;
 pub mod wireless;
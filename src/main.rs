@@ -1,6 +1,6 @@
 #![allow(clippy::uninlined_format_args)]
 
-use esp_idf_hal::{delay::FreeRtos, gpio, prelude::*, timer::*};
+use esp_idf_hal::{delay::FreeRtos, gpio, peripheral::Peripheral, prelude::*, timer::*};
 use esp_idf_svc::{
     bt::{
         ble::{
@@ -10,13 +10,68 @@ use esp_idf_svc::{
         Ble, BtDriver, BtUuid,
     },
     nvs::{self, EspDefaultNvsPartition},
+    sys::EspError,
+};
+use otgi::{
+    can::CanFilter,
+    custom_pid::CustomPid,
+    fuel, obd,
+    scheduler::{self, Priority},
+    sniffer::Sniffer,
+    twai,
+    units::{Quantity, Unit},
+    wireless,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
 };
-use otgi::{obd, wireless};
-use std::sync::Arc;
 
 const SERVICE_UUID: u128 = 0x2cbc6002370f577a928681e04f368400;
 const FUEL_USAGE_CHARACTERISTIC_UUID: u128 = 0x56c46fef90390803a71feebcc8650e43;
 const RUNCOUNT_CHARACTERISTIC_UUID: u128 = 0xed0cdaa9fc55c2c193a061b6e1f36720;
+const FUEL_RATE_CHARACTERISTIC_UUID: u128 = 0x47a7ffd6450a48ad989e066f5e75613b;
+const CLEAR_DTC_CHARACTERISTIC_UUID: u128 = 0x9b0e6c1f2d7a4e0b8f3c51a6d2e47b90;
+const VIN_CHARACTERISTIC_UUID: u128 = 0x3e5a9d4c7b2f41e6a0d8c6f1b94e2a57;
+const READINESS_CHARACTERISTIC_UUID: u128 = 0xc41f7a2e95b84d3c8e6a0f2b7d19e358;
+const CUSTOM_PIDS_CHARACTERISTIC_UUID: u128 = 0x535c04a5b2f247428ae1194ae95bd908;
+const SNIFFER_CHARACTERISTIC_UUID: u128 = 0x37ea4c2c8c714f1cac72a3ecae86f880;
+
+// Clearing DTCs takes two writes: a request, answered with a challenge that must be written back
+const CLEAR_REQUEST: u8 = 0x01;
+const CLEAR_CONFIRM: u8 = 0x02;
+const CLEAR_CHALLENGE: u8 = 0x01;
+const CLEAR_RESULT: u8 = 0x02;
+
+// Custom PID definitions are uploaded as CSV in chunks: begin, any number of data writes, then
+// commit, which is answered with the result. Values are indicated by definition index.
+const PIDS_BEGIN: u8 = 0x01;
+const PIDS_DATA: u8 = 0x02;
+const PIDS_COMMIT: u8 = 0x03;
+const PIDS_RESULT: u8 = 0x01;
+const PIDS_VALUE: u8 = 0x02;
+/// Largest definition file kept in NVS
+const MAX_CUSTOM_PIDS_LEN: usize = 4096;
+/// How often custom PIDs are read while the car is on
+const CUSTOM_PIDS_PERIOD: Duration = Duration::from_secs(1);
+
+// Sniffer mode is started with the protocol to listen at followed by any number of
+// CanFilter::to_le_bytes, and takes effect after a restart
+const SNIFFER_START: u8 = 0x01;
+const SNIFFER_STOP: u8 = 0x02;
+/// Protocol and up to 16 filters
+const MAX_SNIFFER_CONFIG_LEN: usize = 1 + 16 * 9;
+/// Captured frames are printed as a candump log for this interface so `canplayer` can replay it
+const SNIFFER_INTERFACE: &str = "can0";
+
+/// Protocol detection is retried after this long, doubling up to the maximum while no car answers
+const DETECTION_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const DETECTION_BACKOFF_MAX: Duration = Duration::from_secs(30);
+/// How often BLE writes are checked while waiting to retry detection
+const DETECTION_IDLE_PERIOD: Duration = Duration::from_millis(100);
+
+/// How often the driver's error counters and the bus state are logged
+const DIAGNOSTICS_PERIOD: Duration = Duration::from_secs(60);
 
 fn main() {
     esp_idf_svc::sys::link_patches();
@@ -43,10 +98,34 @@ fn main() {
         rc + 1
     };
 
+    // VIN of the last car seen so trips can be tagged before it is read again
+    let mut vin_buf = [0; 18];
+    let last_vin = nvs_namespace
+        .get_str("vin", &mut vin_buf)
+        .unwrap()
+        .unwrap_or_default()
+        .to_string();
+
+    // Definitions were checked before they were stored
+    let mut custom_pids_buf = vec![0; MAX_CUSTOM_PIDS_LEN];
+    let mut custom_pids = nvs_namespace
+        .get_blob("custom_pids", &mut custom_pids_buf)
+        .unwrap()
+        .and_then(|csv| std::str::from_utf8(csv).ok())
+        .map(|csv| CustomPid::parse_csv(csv).unwrap_or_default())
+        .unwrap_or_default();
+    log::info!("Loaded {} custom PIDs", custom_pids.len());
+
     let mut liters_used: f64 = 0.0;
 
     let fuel_usage_uuid = BtUuid::uuid128(FUEL_USAGE_CHARACTERISTIC_UUID);
     let runcount_uuid = BtUuid::uuid128(RUNCOUNT_CHARACTERISTIC_UUID);
+    let fuel_rate_uuid = BtUuid::uuid128(FUEL_RATE_CHARACTERISTIC_UUID);
+    let clear_dtc_uuid = BtUuid::uuid128(CLEAR_DTC_CHARACTERISTIC_UUID);
+    let vin_uuid = BtUuid::uuid128(VIN_CHARACTERISTIC_UUID);
+    let readiness_uuid = BtUuid::uuid128(READINESS_CHARACTERISTIC_UUID);
+    let custom_pids_uuid = BtUuid::uuid128(CUSTOM_PIDS_CHARACTERISTIC_UUID);
+    let sniffer_uuid = BtUuid::uuid128(SNIFFER_CHARACTERISTIC_UUID);
     let ble_server = wireless::Server::new(
         Arc::new(EspBleGap::new(bt.clone()).unwrap()),
         Arc::new(EspGatts::new(bt.clone()).unwrap()),
@@ -69,6 +148,52 @@ fn main() {
                         max_len: 200,
                         data: runcount.to_le_bytes().to_vec(),
                     },
+                    // Sent as the value followed by its unit so the phone can convert it
+                    wireless::CharacteristicDescriptor {
+                        uuid: fuel_rate_uuid.clone(),
+                        permissions: Permission::Write | Permission::Read,
+                        properties: Property::Indicate.into(),
+                        max_len: 200,
+                        data: Quantity::new(0.0, Unit::LitersPerHour)
+                            .to_le_bytes()
+                            .to_vec(),
+                    },
+                    wireless::CharacteristicDescriptor {
+                        uuid: clear_dtc_uuid.clone(),
+                        permissions: Permission::Write | Permission::Read,
+                        properties: Property::Write | Property::Indicate,
+                        max_len: 200,
+                        data: vec![],
+                    },
+                    wireless::CharacteristicDescriptor {
+                        uuid: vin_uuid.clone(),
+                        permissions: Permission::Read.into(),
+                        properties: Property::Indicate | Property::Read,
+                        max_len: 200,
+                        data: last_vin.into_bytes(),
+                    },
+                    // MonitorStatus::to_bytes: MIL, DTC count and readiness monitors
+                    wireless::CharacteristicDescriptor {
+                        uuid: readiness_uuid.clone(),
+                        permissions: Permission::Read.into(),
+                        properties: Property::Indicate | Property::Read,
+                        max_len: 200,
+                        data: vec![],
+                    },
+                    wireless::CharacteristicDescriptor {
+                        uuid: custom_pids_uuid.clone(),
+                        permissions: Permission::Write | Permission::Read,
+                        properties: Property::Write | Property::Indicate,
+                        max_len: 200,
+                        data: vec![],
+                    },
+                    wireless::CharacteristicDescriptor {
+                        uuid: sniffer_uuid.clone(),
+                        permissions: Permission::Write.into(),
+                        properties: Property::Write.into(),
+                        max_len: 200,
+                        data: vec![],
+                    },
                 ],
             }],
             name: "OTGI",
@@ -98,100 +223,441 @@ fn main() {
     let mut high_ref = gpio::PinDriver::output(pins.gpio25).unwrap();
     high_ref.set_high().unwrap();
 
-    let mut driver = obd::ObdDriver::try_new(
-        peripherals.can,
-        pins.gpio33,
-        pins.gpio32,
-        &Default::default(),
-    )
-    .unwrap();
-    driver.start().expect("Failed to start driver");
+    let mut can = peripherals.can;
+    let mut tx = pins.gpio33;
+    let mut rx = pins.gpio32;
 
-    let mut timer = TimerDriver::new(peripherals.timer00, &TimerConfig::new()).unwrap();
-    let timer_hz = timer.tick_hz() as f64;
+    // In sniffer mode nothing is ever sent to the car, the bus is only logged until sniffing is
+    // stopped over BLE. Captures share the UART with the logger, so logging is turned off once
+    // sniffing starts to leave a clean candump log for `canplayer` or `log2asc`.
+    let mut sniffer_config = [0; MAX_SNIFFER_CONFIG_LEN];
+    if let Some(config) = nvs_namespace
+        .get_blob("sniffer", &mut sniffer_config)
+        .unwrap()
+    {
+        let protocol = obd::Protocol::from_repr(config[0]).unwrap_or_default();
+        let filters: Vec<CanFilter> = config[1..]
+            .chunks_exact(9)
+            .filter_map(|filter| CanFilter::from_le_bytes(filter.try_into().unwrap()))
+            .collect();
+        // The controller has a single hardware filter, more are applied in software
+        let hardware_filter = match filters[..] {
+            [filter] => filter,
+            _ => CanFilter::accept_all(),
+        };
+
+        let mut transport = twai::TwaiTransport::try_new(
+            &mut can,
+            &mut tx,
+            &mut rx,
+            &twai::TwaiConfig::listen_only(protocol, hardware_filter),
+        )
+        .unwrap();
+        transport.start().unwrap();
+        let mut sniffer = Sniffer::try_new(transport, filters).unwrap();
+        log::info!("Sniffing {} with filters {:?}", protocol, sniffer.filters());
+        log::set_max_level(log::LevelFilter::Off);
+        // SAFETY: the tag is a valid NUL terminated string
+        unsafe {
+            esp_idf_svc::sys::esp_log_level_set(
+                c"*".as_ptr(),
+                esp_idf_svc::sys::esp_log_level_t_ESP_LOG_NONE,
+            )
+        };
+
+        loop {
+            // Errors can't be reported without corrupting the capture
+            if let Ok(Some(captured)) = sniffer.capture(Duration::from_millis(100)) {
+                println!("{}", captured.to_candump(SNIFFER_INTERFACE));
+            }
+            if let Some(write) = ble_server.take_write(&sniffer_uuid) {
+                configure_sniffer(&nvs_namespace, &write);
+            }
+        }
+    }
 
-    let mut ltft = 0.0;
-    let mut stft = 0.0;
+    // Start with the protocol of the last car seen, falling back to probing all of them
+    let mut preferred = nvs_namespace
+        .get_u8("protocol")
+        .unwrap()
+        .and_then(obd::Protocol::from_repr);
+    // Detected once the car answers, BLE writes are still handled until then
+    let mut driver = None;
+    let mut detection_due = Instant::now();
+    let mut detection_backoff = DETECTION_BACKOFF_MIN;
+    // Whether the hardware filter was turned off for the custom PIDs when the driver was opened
+    let mut filter_off = false;
 
-    let mut stft_last_updated = 0.0;
-    let mut ltft_last_updated = 0.0;
+    let mut timer = TimerDriver::new(peripherals.timer00, &TimerConfig::new()).unwrap();
+    let timer_hz = timer.tick_hz() as f64;
 
     let mut fuel_usage_last_updated = 0.0;
 
     let mut timer_enabled = false;
 
-    let stft_query = obd::ObdQuery::new(
-        obd::ObdMode::QueryNow,
-        Some(obd::PID::ShortTermFuelTrimBankOne),
-    );
-    let ltft_query = obd::ObdQuery::new(
-        obd::ObdMode::QueryNow,
-        Some(obd::PID::ShortTermFuelTrimBankOne),
-    );
-    let maf_query = obd::ObdQuery::new(
-        obd::ObdMode::QueryNow,
-        Some(obd::PID::ShortTermFuelTrimBankOne),
-    );
+    // MAF drives the fuel rate so it's polled the most, the trims change slowly
+    let mut scheduler = scheduler::Scheduler::new(Duration::from_millis(50));
+    scheduler.register(obd::PID::MassAirFlow, 10.0, Priority::High);
+    scheduler.register(obd::PID::ShortTermFuelTrimBankOne, 5.0, Priority::Normal);
+    scheduler.register(obd::PID::LongTermFuelTrimBankOne, 1.0, Priority::Low);
+    scheduler.register(obd::PID::VehicleSpeed, 2.0, Priority::Low);
+    scheduler.subscribe(|pid, sample| log::debug!("{:?}: {}", pid, sample.value));
+
+    let monitor_status_query =
+        obd::ObdQuery::new(obd::ObdMode::QueryNow, Some(obd::PID::MonitorStatus));
+
+    let mut clear_challenge = obd::ClearChallenge::default();
+    // Read when the car is first seen and again after codes are cleared
+    let mut readiness_stale = true;
+    let mut diagnostics_logged = Instant::now();
+    let mut custom_pids_read = Instant::now();
+    // `None` when no upload is in progress or it grew too large
+    let mut custom_pids_upload: Option<Vec<u8>> = None;
 
     loop {
-        let mut time = timer.counter().unwrap() as f64 / timer_hz;
-        // Update stft at 5 Hz
-        if timer_enabled && time > stft_last_updated + 0.2 {
-            if let Ok(obd::ObdReadableData::SignedPercentage(stft_res)) = driver.query(&stft_query)
-            {
-                stft = stft_res;
-                log::info!("Updated stft: {:?}", stft);
-                stft_last_updated = time;
-                FreeRtos::delay_ms(50);
-                time = timer.counter().unwrap() as f64 / timer_hz;
+        let next = match driver {
+            Some(_) => scheduler.next_poll(),
+            None => Some(detection_due.min(Instant::now() + DETECTION_IDLE_PERIOD)),
+        };
+        if let Some(next) = next {
+            let wait = next.saturating_duration_since(Instant::now());
+            FreeRtos::delay_ms(wait.as_millis() as u32);
+        }
+
+        if driver.is_none() && Instant::now() >= detection_due {
+            // Custom PID headers answered with the other ID length need the hardware filter off
+            let needs_filter_off =
+                |protocol: obd::Protocol| !answers_pass_filter(protocol.addressing(), &custom_pids);
+            let mut open = |protocol: obd::Protocol| -> Result<_, EspError> {
+                let mut config = twai::TwaiConfig::for_protocol(protocol);
+                if needs_filter_off(protocol) {
+                    config = config.with_filter(CanFilter::accept_all());
+                }
+                // SAFETY: `detect` drops the previous transport, releasing these peripherals,
+                // before opening the next one, and no driver is left from an earlier detection
+                let mut transport = unsafe {
+                    twai::TwaiTransport::try_new(
+                        can.clone_unchecked(),
+                        tx.clone_unchecked(),
+                        rx.clone_unchecked(),
+                        &config,
+                    )
+                }?;
+                transport.start()?;
+                Ok(transport)
+            };
+            match obd::ObdDriver::detect(&mut open, preferred, &Default::default()) {
+                Ok(detected) => {
+                    let protocol = detected.protocol().unwrap();
+                    log::info!("Using {}", protocol);
+                    filter_off = needs_filter_off(protocol);
+                    if preferred != Some(protocol) {
+                        nvs_namespace.set_u8("protocol", protocol as u8).unwrap();
+                        preferred = Some(protocol);
+                    }
+                    detection_backoff = DETECTION_BACKOFF_MIN;
+                    driver = Some(detected);
+                }
+                Err(e) => {
+                    log::warn!("Couldn't detect the OBD protocol, is the car on? {:?}", e);
+                    detection_due = Instant::now() + detection_backoff;
+                    detection_backoff = (detection_backoff * 2).min(DETECTION_BACKOFF_MAX);
+                }
             }
         }
 
-        // Update ltft at 1 Hz
-        if timer_enabled && time > ltft_last_updated + 1.0 {
-            if let Ok(obd::ObdReadableData::SignedPercentage(ltft_res)) = driver.query(&ltft_query)
-            {
-                ltft = ltft_res;
-                log::info!("Updated ltft");
-                ltft_last_updated = time;
+        if let Some(driver) = &mut driver {
+            let now = Instant::now();
+            if let Err(e) = scheduler.poll(driver, now) {
+                log::debug!("Poll failed: {:?}", e);
+            }
+            if now.saturating_duration_since(diagnostics_logged) >= DIAGNOSTICS_PERIOD {
+                diagnostics_logged = now;
+                log::info!("{:?}, {:?}", driver.stats(), driver.bus_status());
+            }
+            let mut time = timer.counter().unwrap() as f64 / timer_hz;
+
+            let latest = |pid, unit| {
+                scheduler
+                    .latest(pid)
+                    .and_then(|sample| sample.value.to(unit))
+            };
+            // Only act on MAF when this poll brought a new sample
+            let maf = scheduler
+                .latest(obd::PID::MassAirFlow)
+                .filter(|sample| sample.at == now)
+                .and_then(|sample| sample.value.to(Unit::GramsPerSecond));
+
+            if let Some(maf) = maf {
+                // Only start timer once data is being read to avoid assuming a massive fuel usage if
+                // the esp is booted before the car
+                if !timer_enabled {
+                    timer_enabled = true;
+                    timer.enable(true).unwrap();
+
+                    // Find out what the car supports so unsupported PIDs aren't polled
+                    if let Err(e) = driver.discover_supported_pids() {
+                        log::error!("Couldn't discover supported PIDs: {:?}", e);
+                    }
+                    log::info!("Supported PIDs: {:?}", driver.supported_pids());
+                    FreeRtos::delay_ms(50);
+
+                    match driver.read_vin() {
+                        Ok(vin) => {
+                            log::info!("VIN: {}", vin);
+                            nvs_namespace.set_str("vin", vin).unwrap();
+                            ble_server.indicate(&vin_uuid, vin.as_bytes()).unwrap();
+                        }
+                        Err(e) => log::error!("Couldn't read VIN: {:?}", e),
+                    }
+                    FreeRtos::delay_ms(50);
+
+                    // Read diagnostic codes on startup
+                    for mode in [
+                        obd::ObdMode::QueryDTC,
+                        obd::ObdMode::QueryPendingDTC,
+                        obd::ObdMode::QueryPermanentDTC,
+                    ] {
+                        let (kind, codes) = match driver.query(&obd::ObdQuery::new(mode, None)) {
+                            Ok(obd::ObdReadableData::DTC(codes)) => ("stored", codes),
+                            Ok(obd::ObdReadableData::PendingDTC(codes)) => ("pending", codes),
+                            Ok(obd::ObdReadableData::PermanentDTC(codes)) => ("permanent", codes),
+                            other => {
+                                log::error!("Couldn't read {:?} codes: {:?}", mode, other);
+                                continue;
+                            }
+                        };
+                        for code in codes {
+                            log::info!(
+                                "Read {} DTC {}: {}",
+                                kind,
+                                code,
+                                code.description().unwrap_or("unknown")
+                            );
+                        }
+                        FreeRtos::delay_ms(50);
+                    }
+
+                    // Conditions when the check engine light came on
+                    match driver.read_freeze_frame(
+                        0,
+                        &[
+                            obd::PID::EngineSpeed,
+                            obd::PID::VehicleSpeed,
+                            obd::PID::CalculatedEngineLoad,
+                            obd::PID::EngineCoolantTemperature,
+                            obd::PID::ShortTermFuelTrimBankOne,
+                            obd::PID::LongTermFuelTrimBankOne,
+                        ],
+                    ) {
+                        Ok(Some(frame)) => {
+                            log::info!("Freeze frame stored by {}", frame.dtc);
+                            for (pid, value) in frame.values {
+                                log::info!("  {:?}: {}", pid, value);
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => log::error!("Couldn't read freeze frame: {:?}", e),
+                    }
+                    FreeRtos::delay_ms(50);
+                    time = timer.counter().unwrap() as f64 / timer_hz;
+                }
+
+                // The trims keep their last value if a reply leaves them out
+                let stft = latest(obd::PID::ShortTermFuelTrimBankOne, Unit::Percent).unwrap_or(0.0);
+                let ltft = latest(obd::PID::LongTermFuelTrimBankOne, Unit::Percent).unwrap_or(0.0);
+                let usage = fuel::fuel_rate(maf, stft, ltft);
+                liters_used += f64::from(usage / 3600.0) * (time - fuel_usage_last_updated);
+                fuel_usage_last_updated = time;
+
+                ble_server
+                    .indicate(
+                        &fuel_rate_uuid,
+                        &Quantity::new(usage, Unit::LitersPerHour).to_le_bytes(),
+                    )
+                    .unwrap();
+            } else if timer_enabled && scheduler.is_stale(obd::PID::MassAirFlow, now) {
+                // If the car is turned off and then back on, we should restart the timer
+                fuel_usage_last_updated = 0.0;
+                readiness_stale = true;
+                timer.enable(false).unwrap();
+                timer_enabled = false;
+            }
+
+            ble_server
+                .indicate(&fuel_usage_uuid, &liters_used.to_le_bytes())
+                .unwrap();
+
+            if timer_enabled && readiness_stale && driver.is_supported(obd::PID::MonitorStatus) {
+                match driver.query(&monitor_status_query) {
+                    Ok(obd::ObdReadableData::MonitorStatus(status)) => {
+                        log::info!(
+                            "MIL {}, {} DTCs, incomplete monitors: {:?}",
+                            if status.mil { "on" } else { "off" },
+                            status.dtc_count,
+                            status.incomplete().collect::<Vec<_>>()
+                        );
+                        ble_server
+                            .indicate(&readiness_uuid, &status.to_bytes())
+                            .unwrap();
+                        readiness_stale = false;
+                    }
+                    other => log::error!("Couldn't read monitor status: {:?}", other),
+                }
                 FreeRtos::delay_ms(50);
             }
+
+            if timer_enabled
+                && now.saturating_duration_since(custom_pids_read) >= CUSTOM_PIDS_PERIOD
+            {
+                custom_pids_read = now;
+                for (i, pid) in custom_pids.iter().enumerate() {
+                    match driver.query_custom(pid) {
+                        Ok(value) => {
+                            log::debug!("{}: {}", pid.name, value);
+                            let mut indication = vec![PIDS_VALUE, i as u8];
+                            indication.extend(value.to_le_bytes());
+                            ble_server.indicate(&custom_pids_uuid, &indication).unwrap();
+                        }
+                        Err(e) => log::debug!("Couldn't read {}: {:?}", pid.name, e),
+                    }
+                }
+            }
         }
 
-        let maf = driver.query(&maf_query);
-        FreeRtos::delay_ms(50);
-        time = timer.counter().unwrap() as f64 / timer_hz;
-
-        if let Ok(obd::ObdReadableData::Raw(maf)) = maf {
-            // Only start timer once data is being read to avoid assuming a massive fuel usage if
-            // the esp is booted before the car
-            if !timer_enabled {
-                timer_enabled = true;
-                timer.enable(true).unwrap();
-
-                // Read diagnostic codes on startup
-                let codes = driver
-                    .query(&obd::ObdQuery::new(obd::ObdMode::QueryDTC, None))
-                    .expect("couldn't read DTC");
-                log::info!("Read DTC codes: {:#?}", codes);
-                FreeRtos::delay_ms(50);
-                time = timer.counter().unwrap() as f64 / timer_hz;
+        match ble_server.take_write(&custom_pids_uuid).as_deref() {
+            Some([PIDS_BEGIN]) => custom_pids_upload = Some(vec![]),
+            Some([PIDS_DATA, data @ ..]) => {
+                if let Some(upload) = &mut custom_pids_upload {
+                    upload.extend_from_slice(data);
+                    if upload.len() > MAX_CUSTOM_PIDS_LEN {
+                        log::warn!("Custom PID definitions are too large");
+                        custom_pids_upload = None;
+                    }
+                }
             }
+            Some([PIDS_COMMIT]) => {
+                let mut restart_for_filter = false;
+                // The line of the first error, 0 if the upload itself was bad
+                let parsed =
+                    match custom_pids_upload.take().map(String::from_utf8) {
+                        Some(Ok(csv)) => CustomPid::parse_csv(&csv)
+                            .map(|pids| (csv, pids))
+                            .map_err(|e| {
+                                log::error!("Invalid custom PID definitions: {:?}", e);
+                                e.line as u16
+                            }),
+                        _ => Err(0),
+                    };
+                let result = match parsed {
+                    Ok((csv, pids)) => {
+                        nvs_namespace
+                            .set_blob("custom_pids", csv.as_bytes())
+                            .unwrap();
+                        log::info!("Stored {} custom PIDs", pids.len());
+                        // Before detection the next transport is opened with the new PIDs
+                        restart_for_filter = driver
+                            .as_ref()
+                            .and_then(obd::ObdDriver::protocol)
+                            .is_some_and(|protocol| {
+                                !filter_off && !answers_pass_filter(protocol.addressing(), &pids)
+                            });
+                        custom_pids = pids;
+                        vec![PIDS_RESULT, 0x00, custom_pids.len() as u8]
+                    }
+                    Err(line) => {
+                        let mut result = vec![PIDS_RESULT, 0x01];
+                        result.extend(line.to_le_bytes());
+                        result
+                    }
+                };
+                ble_server.indicate(&custom_pids_uuid, &result).unwrap();
+                if restart_for_filter {
+                    log::info!("Restarting to let the answers to the new custom PIDs through");
+                    // Let the indication go out first
+                    FreeRtos::delay_ms(100);
+                    esp_idf_hal::reset::restart();
+                }
+            }
+            Some(other) => log::warn!("Ignoring custom PID write: {:?}", other),
+            None => {}
+        }
 
-            let usage = (maf * 3600.0) / ((14.7 * (1.0 + ((stft + ltft) / 100.0))) * 740.0);
-            liters_used += f64::from(usage / 3600.0) * (time - fuel_usage_last_updated);
-            fuel_usage_last_updated = time;
-        } else if timer_enabled {
-            // If the car is turned off and then back on, we should restart the timer
-            stft_last_updated = 0.0;
-            ltft_last_updated = 0.0;
-            fuel_usage_last_updated = 0.0;
-            timer.enable(false).unwrap();
-            timer_enabled = false;
+        if let Some(write) = ble_server.take_write(&sniffer_uuid) {
+            configure_sniffer(&nvs_namespace, &write);
         }
 
-        ble_server
-            .indicate(&fuel_usage_uuid, &liters_used.to_le_bytes())
-            .unwrap();
+        // Clearing also resets the readiness monitors, so the phone has to echo a challenge back
+        // before anything is sent to the car
+        match ble_server.take_write(&clear_dtc_uuid).as_deref() {
+            Some([CLEAR_REQUEST]) => {
+                // SAFETY: esp_random has no preconditions; with the radio on it's a true RNG
+                let random = unsafe { esp_idf_svc::sys::esp_random() };
+                let nonce = clear_challenge.issue(random, Instant::now());
+                let mut challenge = vec![CLEAR_CHALLENGE];
+                challenge.extend(nonce.to_le_bytes());
+                ble_server.indicate(&clear_dtc_uuid, &challenge).unwrap();
+            }
+            Some([CLEAR_CONFIRM, nonce @ ..]) => {
+                let cleared = match nonce.try_into() {
+                    Ok(nonce) => clear_challenge.confirm(u32::from_le_bytes(nonce), Instant::now()),
+                    Err(_) => None,
+                }
+                .map(|confirmation| match &mut driver {
+                    Some(driver) => driver.clear_dtcs(confirmation),
+                    None => Err(obd::ObdError::ProtocolNotDetected),
+                });
+
+                let result = match cleared {
+                    Some(Ok(ecus)) => {
+                        log::info!("Cleared DTCs on {} ECUs", ecus.len());
+                        readiness_stale = true;
+                        [CLEAR_RESULT, 0x00, ecus.len() as u8]
+                    }
+                    Some(Err(e)) => {
+                        log::error!("Couldn't clear DTCs: {:?}", e);
+                        [CLEAR_RESULT, 0x01, 0]
+                    }
+                    None => {
+                        log::warn!("Refusing to clear DTCs without a valid confirmation");
+                        [CLEAR_RESULT, 0x02, 0]
+                    }
+                };
+                ble_server.indicate(&clear_dtc_uuid, &result).unwrap();
+            }
+            Some(other) => log::warn!("Ignoring clear DTC write: {:?}", other),
+            None => {}
+        }
+    }
+}
+
+/// Whether the hardware filter for `addressing` lets the answer to every custom PID through
+fn answers_pass_filter(addressing: obd::Addressing, pids: &[CustomPid]) -> bool {
+    let filter = addressing.diagnostic_filter();
+    pids.iter()
+        .filter_map(CustomPid::response_id)
+        .all(|id| filter.matches(id))
+}
+
+/// Stores or removes the sniffer configuration written over BLE and restarts to apply it
+fn configure_sniffer(nvs_namespace: &nvs::EspNvs<nvs::NvsDefault>, write: &[u8]) {
+    match write {
+        [SNIFFER_START, config @ ..]
+            if !config.is_empty()
+                && config.len() <= MAX_SNIFFER_CONFIG_LEN
+                && (config.len() - 1) % 9 == 0 =>
+        {
+            nvs_namespace.set_blob("sniffer", config).unwrap();
+        }
+        [SNIFFER_STOP] => {
+            nvs_namespace.remove("sniffer").unwrap();
+        }
+        other => {
+            log::warn!("Ignoring sniffer write: {:?}", other);
+            return;
+        }
     }
+
+    log::info!("Restarting to apply the sniffer configuration");
+    esp_idf_hal::reset::restart();
 }
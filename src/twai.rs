@@ -0,0 +1,195 @@
+use esp_idf_hal::{
+    can, delay,
+    gpio::{InputPin, OutputPin},
+    peripheral::Peripheral,
+    sys::{self, esp, EspError, ESP_ERR_TIMEOUT},
+};
+use otgi_core::{
+    can::{BusState, BusStatus, CanFilter, CanFrame, CanId, CanTransport},
+    obd::{Addressing, Protocol},
+};
+use std::time::{Duration, Instant};
+
+/// Bus-off recovery waits for 128 occurrences of 11 recessive bits, a few ms at 250 kbit/s
+const RECOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub struct TwaiConfig {
+    pub timing: can::config::Timing,
+    pub filter: can::config::Filter,
+    pub mode: can::config::Mode,
+}
+
+/// The hardware equivalent of `filter`; both only compare the bits set in the mask
+fn hardware_filter(filter: CanFilter) -> can::config::Filter {
+    if filter.extended {
+        can::config::Filter::Extended {
+            filter: filter.id,
+            mask: filter.mask,
+        }
+    } else {
+        can::config::Filter::Standard {
+            filter: filter.id as u16,
+            mask: filter.mask as u16,
+        }
+    }
+}
+
+impl TwaiConfig {
+    /// 500 kbit/s with a hardware filter letting through diagnostic responses using
+    /// `addressing`, including those to custom PID headers
+    pub fn for_addressing(addressing: Addressing) -> Self {
+        Self {
+            timing: can::config::Timing::B500K,
+            filter: hardware_filter(addressing.diagnostic_filter()),
+            mode: can::config::Mode::Normal,
+        }
+    }
+
+    /// Replaces the hardware filter
+    pub fn with_filter(self, filter: CanFilter) -> Self {
+        Self {
+            filter: hardware_filter(filter),
+            ..self
+        }
+    }
+
+    /// Receives whatever passes `filter` at the protocol's bitrate without ever acknowledging or
+    /// sending a frame, so the bus can be observed without disturbing it
+    pub fn listen_only(protocol: Protocol, filter: CanFilter) -> Self {
+        Self {
+            mode: can::config::Mode::ListenOnly,
+            ..Self::for_protocol(protocol).with_filter(filter)
+        }
+    }
+
+    pub fn for_protocol(protocol: Protocol) -> Self {
+        let timing = match protocol.bitrate() {
+            250_000 => can::config::Timing::B250K,
+            _ => can::config::Timing::B500K,
+        };
+
+        Self {
+            timing,
+            ..Self::for_addressing(protocol.addressing())
+        }
+    }
+}
+
+impl Default for TwaiConfig {
+    fn default() -> Self {
+        Self::for_protocol(Protocol::default())
+    }
+}
+
+/// [`CanTransport`] backed by the ESP32's TWAI controller
+pub struct TwaiTransport<'a> {
+    can_driver: can::CanDriver<'a>,
+    // The hardware filter can only be changed by reinstalling the driver, so anything set
+    // through `set_filter` is applied in software on top of it
+    filter: CanFilter,
+}
+
+impl<'a> TwaiTransport<'a> {
+    pub fn try_new(
+        can: impl Peripheral<P = can::CAN> + 'a,
+        tx: impl Peripheral<P = impl OutputPin> + 'a,
+        rx: impl Peripheral<P = impl InputPin> + 'a,
+        config: &TwaiConfig,
+    ) -> Result<Self, EspError> {
+        Ok(Self {
+            can_driver: can::CanDriver::new(
+                can,
+                tx,
+                rx,
+                &can::config::Config::new()
+                    .filter(config.filter)
+                    .timing(config.timing)
+                    .mode(config.mode),
+            )?,
+            filter: CanFilter::accept_all(),
+        })
+    }
+
+    pub fn start(&mut self) -> Result<(), EspError> {
+        self.can_driver.start()
+    }
+}
+
+impl CanTransport for TwaiTransport<'_> {
+    type Error = EspError;
+
+    fn transmit(&mut self, frame: &CanFrame, timeout: Duration) -> Result<(), Self::Error> {
+        let flags = if frame.id().is_extended() {
+            can::Flags::Extended.into()
+        } else {
+            can::Flags::None.into()
+        };
+        let tx_frame = can::Frame::new(frame.id().raw(), flags, frame.data())
+            .expect("CAN frames hold at most 8 bytes");
+
+        self.can_driver
+            .transmit(&tx_frame, delay::TickType::from(timeout).into())
+    }
+
+    fn receive(&mut self, timeout: Duration) -> Result<Option<CanFrame>, Self::Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let rx_frame = match self
+                .can_driver
+                .receive(delay::TickType::from(remaining).into())
+            {
+                Ok(frame) => frame,
+                Err(e) if e.code() == ESP_ERR_TIMEOUT => return Ok(None),
+                Err(e) => return Err(e),
+            };
+
+            let id = if rx_frame.is_extended() {
+                CanId::Extended(rx_frame.identifier())
+            } else {
+                CanId::Standard(rx_frame.identifier() as u16)
+            };
+
+            if self.filter.matches(id) {
+                return Ok(CanFrame::new(id, rx_frame.data()));
+            }
+        }
+    }
+
+    fn set_filter(&mut self, filter: CanFilter) -> Result<(), Self::Error> {
+        self.filter = filter;
+        Ok(())
+    }
+
+    fn status(&mut self) -> Result<BusStatus, Self::Error> {
+        let mut info = sys::twai_status_info_t::default();
+        esp!(unsafe { sys::twai_get_status_info(&mut info) })?;
+
+        #[allow(non_upper_case_globals)]
+        let state = match info.state {
+            sys::twai_state_t_TWAI_STATE_BUS_OFF => BusState::BusOff,
+            sys::twai_state_t_TWAI_STATE_RECOVERING => BusState::Recovering,
+            sys::twai_state_t_TWAI_STATE_STOPPED => BusState::Stopped,
+            _ => BusState::Running,
+        };
+        Ok(BusStatus {
+            state,
+            tx_error_counter: info.tx_error_counter,
+            rx_error_counter: info.rx_error_counter,
+        })
+    }
+
+    fn recover(&mut self) -> Result<(), Self::Error> {
+        esp!(unsafe { sys::twai_initiate_recovery() })?;
+
+        // The controller stops once recovered and has to be started again
+        let deadline = Instant::now() + RECOVERY_TIMEOUT;
+        while self.status()?.state != BusState::Stopped {
+            if Instant::now() > deadline {
+                return Err(EspError::from_infallible::<ESP_ERR_TIMEOUT>());
+            }
+            delay::FreeRtos::delay_ms(10);
+        }
+        self.can_driver.start()
+    }
+}
@@ -58,6 +58,7 @@ struct State {
     gatt_intf: Option<GattInterface>,
     ind_confirmed: Option<BdAddr>,
     //service_handle: Option<Handle>,
+    writes: Vec<(BtUuid, Vec<u8>)>,
 }
 
 #[derive(Debug, Clone)]
@@ -168,7 +169,9 @@ impl Server {
                                     },
                                     is_primary: service_descriptor.is_primary,
                                 },
-                                8, // TODO: automatically calculate num_handles
+                                // The service plus a declaration, value and CCCD for each
+                                // characteristic
+                                (1 + 3 * service_descriptor.characteristics.len()) as u16,
                             )
                             .unwrap();
                     });
@@ -323,12 +326,59 @@ impl Server {
                     )
                     .unwrap();
             }
+            GattsEvent::Write {
+                conn_id,
+                trans_id,
+                handle,
+                need_rsp,
+                is_prep: false,
+                value,
+                ..
+            } => {
+                let mut state = self.state.lock().unwrap();
+                let Some(characteristic) = state
+                    .services
+                    .iter_mut()
+                    .flat_map(|s| s.characteristics.iter_mut())
+                    .find(|char| char.handle == handle)
+                else {
+                    // Descriptor writes such as enabling indications
+                    info!("Received write to handle {:?}", handle);
+                    return;
+                };
+
+                characteristic.data = value.to_vec();
+                let uuid = characteristic.uuid.clone();
+                state.writes.push((uuid, value.to_vec()));
+
+                if need_rsp {
+                    self.gatts
+                        .send_response(
+                            state.gatt_intf.unwrap(),
+                            conn_id,
+                            trans_id,
+                            GattStatus::Ok,
+                            None,
+                        )
+                        .unwrap();
+                }
+            }
             _ => {
                 info!("Received GATT event: {:?}", event)
             }
         }
     }
 
+    /// Takes the oldest value written by a peer to the characteristic, if any
+    pub fn take_write(&self, characteristic_uuid: &BtUuid) -> Option<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        let i = state
+            .writes
+            .iter()
+            .position(|(uuid, _)| uuid == characteristic_uuid)?;
+        Some(state.writes.remove(i).1)
+    }
+
     pub fn indicate(&self, characteristic_uuid: &BtUuid, data: &[u8]) -> Result<(), EspError> {
         let mut state = self.state.lock().unwrap();
         let characteristic = state